@@ -0,0 +1,108 @@
+use catr::Config;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const LINES: usize = 200_000;
+
+// --------------------------------------------------
+fn gen_input() -> PathBuf {
+    let path = env::temp_dir().join("catr-bench-input.txt");
+    let mut file = BufWriter::new(File::create(&path).unwrap());
+    for i in 0..LINES {
+        writeln!(file, "{i}\tThe quick brown fox jumps over the lazy dog.").unwrap();
+    }
+    file.flush().unwrap();
+    path
+}
+
+// --------------------------------------------------
+/// What `io::stdout()` is: a line buffered writer behind a lock that every
+/// write takes again.
+struct Stdout(Mutex<LineWriter<File>>);
+
+impl Write for &Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
+
+// --------------------------------------------------
+/// The formatting loop of `run` before output went through one buffered
+/// writer, cut down to `-n -T`: every line is written piece by piece into
+/// stdout.
+fn unbuffered_cat(input: &Path, stdout: &Stdout) -> io::Result<()> {
+    let mut stdout = stdout;
+    let mut file = BufReader::new(File::open(input)?);
+    let mut line_num = 0;
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        if file.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        line_num += 1;
+
+        let has_newline = line.last() == Some(&b'\n');
+        if has_newline {
+            line.pop();
+        }
+
+        let mut shown = Vec::with_capacity(line.len());
+        for &byte in &line {
+            match byte {
+                b'\t' => shown.extend_from_slice(b"^I"),
+                _ => shown.push(byte)
+            }
+        }
+        line = shown;
+
+        write!(stdout, "{:6}\t", line_num)?;
+        stdout.write_all(&line)?;
+        if has_newline {
+            stdout.write_all(b"\n")?;
+        }
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+fn formatting(c: &mut Criterion) {
+    let input = gen_input();
+    let config = Config::try_from_args(["catr", "-n", "-T", input.to_str().unwrap()])
+        .unwrap();
+
+    let mut group = c.benchmark_group("formatting");
+    group.throughput(Throughput::Bytes(fs::metadata(&input).unwrap().len()));
+    group.sample_size(10);
+
+    group.bench_function("before", |b| {
+        b.iter(|| {
+            let stdout = Stdout(Mutex::new(LineWriter::new(File::create("/dev/null").unwrap())));
+            unbuffered_cat(&input, &stdout).unwrap();
+        })
+    });
+
+    group.bench_function("after", |b| {
+        b.iter(|| {
+            let mut out = BufWriter::with_capacity(
+                64 * 1024, File::create("/dev/null").unwrap()
+            );
+            catr::run_to(&config, &mut out).unwrap();
+        })
+    });
+
+    group.finish();
+    fs::remove_file(input).unwrap();
+}
+
+criterion_group!(benches, formatting);
+criterion_main!(benches);
@@ -0,0 +1,116 @@
+//! `ARCHIVE:MEMBER` inputs: one member of a tar or zip archive, read
+//! without extracting anything.
+//!
+//! Tar members are streamed from the archive, which may be compressed in
+//! any format `decompress` knows. Zip members are decoded in memory first,
+//! zip's directory being at the end of the file.
+
+use crate::{decompress, open_file, Error};
+use std::fs::File;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Tar,
+    Zip
+}
+
+impl Kind {
+    /// The kind of archive `path` names, going by its extension.
+    fn of(path: &str) -> Option<Kind> {
+        const TAR: [&str; 9] = [
+            ".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tbz2", ".tar.xz", ".txz", ".tar.zst", ".tzst"
+        ];
+
+        let path = path.to_ascii_lowercase();
+        if path.ends_with(".zip") {
+            Some(Kind::Zip)
+        } else if TAR.iter().any(|ext| path.ends_with(ext)) {
+            Some(Kind::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Splits `spec` into an archive and a member path, at the first colon
+/// following a tar or zip extension.
+pub fn split(spec: &str) -> Option<(&str, &str)> {
+    spec.match_indices(':')
+        .map(|(i, _)| (&spec[..i], &spec[i + 1..]))
+        .find(|(archive, member)| !member.is_empty() && Kind::of(archive).is_some())
+}
+
+/// Opens `member` of `archive`; `spec` is the name errors are reported
+/// under.
+pub fn open(archive: &str, member: &str, spec: &str) -> Result<Box<dyn Read>, Error> {
+    let file = open_file(archive)?;
+    let member = member.trim_start_matches("./");
+    match Kind::of(archive) {
+        Some(Kind::Zip) => zip_member(file, member, spec),
+        _ => tar_member(file, member, spec)
+    }
+}
+
+fn tar_member(file: File, member: &str, spec: &str) -> Result<Box<dyn Read>, Error> {
+    let read = |source| Error::Read { path: spec.to_string(), source };
+
+    let mut archive = tar::Archive::new(decompress::wrap(file, spec)?);
+    let mut size = None;
+    for entry in archive.entries().map_err(read)? {
+        let entry = entry.map_err(read)?;
+        let path = entry.path().map_err(read)?;
+        if path.strip_prefix("./").unwrap_or(&path) != Path::new(member) {
+            continue;
+        }
+
+        let kind = entry.header().entry_type();
+        if kind.is_dir() {
+            return Err(Error::IsDirectory { path: spec.to_string() });
+        }
+        if !kind.is_file() {
+            return Err(not_regular(spec));
+        }
+        size = Some(entry.size());
+        break;
+    }
+
+    // The archive stops reading right after the header of the last entry
+    // handed out, so what follows is the member's data.
+    match size {
+        Some(size) => Ok(Box::new(archive.into_inner().take(size))),
+        None => Err(not_found(spec))
+    }
+}
+
+fn zip_member(file: File, member: &str, spec: &str) -> Result<Box<dyn Read>, Error> {
+    let read = |source| Error::Read { path: spec.to_string(), source };
+
+    let mut archive = zip::ZipArchive::new(file).map_err(|err| read(err.into()))?;
+    let mut entry = match archive.by_name(member) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Err(not_found(spec)),
+        Err(err) => return Err(read(err.into()))
+    };
+    if entry.is_dir() {
+        return Err(Error::IsDirectory { path: spec.to_string() });
+    }
+    if !entry.is_file() {
+        return Err(not_regular(spec));
+    }
+
+    let mut contents = Vec::new();
+    entry.read_to_end(&mut contents).map_err(read)?;
+    Ok(Box::new(Cursor::new(contents)))
+}
+
+fn not_found(spec: &str) -> Error {
+    let source = io::Error::new(io::ErrorKind::NotFound, "no such member in the archive");
+    Error::Open { path: spec.to_string(), source }
+}
+
+fn not_regular(spec: &str) -> Error {
+    let source = io::Error::new(io::ErrorKind::InvalidInput, "not a regular file in the archive");
+    Error::Open { path: spec.to_string(), source }
+}
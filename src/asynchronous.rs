@@ -0,0 +1,254 @@
+//! Tokio counterparts of `run`, `open` and `Formatter`, behind the `async`
+//! feature. They share the `Pipeline` with the blocking code, so output is
+//! byte for byte the same.
+
+use crate::{
+    copy, formatter, open_file, unsupported, walk, Config, Error, Headers, Pipeline, Summary,
+    OUT_BUF_SIZE
+};
+use std::io::{self, IsTerminal};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter,
+    ReadBuf
+};
+
+/// Same as `run`, writing to tokio's stdout.
+pub async fn run_async(config: Config) -> Result<Summary, Error> {
+    check(&config, "run_async")?;
+    let config = Config { color: config.color.for_stdout(), ..config };
+    let mut out = BufWriter::with_capacity(OUT_BUF_SIZE, tokio::io::stdout());
+    let mut summary = Summary::verbose();
+    let result = cat_async(&config, &mut out, copy::stdout_file_id(), &mut summary).await;
+    let result = match result {
+        Ok(()) => out.flush().await.map_err(|source| Error::Write { source }),
+        Err(err) => Err(err)
+    };
+    summary.finish(result)
+}
+
+/// Same as `run_to`, writing into `out`.
+pub async fn run_async_to<W: AsyncWrite + Unpin>(config: &Config, mut out: W) -> Result<Summary, Error> {
+    check(config, "run_async_to")?;
+    let mut summary = Summary::default();
+    let result = cat_async(config, &mut out, None, &mut summary).await;
+    let result = match result {
+        Ok(()) => out.flush().await.map_err(|source| Error::Write { source }),
+        Err(err) => Err(err)
+    };
+    summary.finish(result)
+}
+
+/// Fails on the options of `config` that only the blocking code reads
+/// inputs for.
+fn check(config: &Config, entry: &'static str) -> Result<(), Error> {
+    unsupported(entry, &[
+        ("--decompress", config.decompress),
+        ("--archive-member", config.archive_members),
+        ("--lines", config.lines.is_some()),
+        ("--bytes", config.bytes.is_some()),
+        ("--hex", config.hex),
+        ("--decode", config.decode.is_some()),
+        ("--encode", config.encode.is_some()),
+        ("--follow", config.follow.is_some())
+    ])
+}
+
+/// Same as `open`, for use with `AsyncFormatter`.
+pub fn open_async(filename: &str) -> Result<Box<dyn AsyncBufRead + Unpin + Send>, Error> {
+    let file = tokio::fs::File::from_std(open_file(filename)?);
+    Ok(Box::new(BufReader::new(file)))
+}
+
+/// `cat` on tokio. `output` identifies the file stdout writes to, if any.
+async fn cat_async<W: AsyncWrite + Unpin>(
+    config: &Config,
+    out: &mut W,
+    output: Option<(u64, u64)>,
+    summary: &mut Summary
+) -> Result<(), Error> {
+    let mut formatter = AsyncFormatter::with_pipeline(Pipeline::from_config(config));
+    let mut headers = Headers::new(config);
+    let mut header = Vec::new();
+    let mut inputs = walk::inputs(config)?.peekable();
+    let mut several = false;
+
+    while let Some(filename) = inputs.next() {
+        several |= inputs.peek().is_some();
+        let opened = filename.and_then(|filename| {
+            open_file(&filename).map(|file| (filename, file))
+        });
+        let (filename, mut file) = match opened {
+            Ok(opened) => opened,
+            Err(err) => {
+                summary.report(err);
+                continue;
+            }
+        };
+
+        if output.is_some_and(|output| copy::is_output(&mut file, output)) {
+            summary.report(Error::InputIsOutput { path: filename });
+            continue;
+        }
+
+        if several && headers.enabled() {
+            header.clear();
+            headers.render(&filename, &mut header);
+            formatter.pipeline.break_line();
+            out.write_all(&header).await.map_err(|source| Error::Write { source })?;
+        }
+
+        let interactive = file.is_terminal();
+        let file = tokio::fs::File::from_std(file);
+        if formatter.pipeline.is_empty() {
+            let mut file = BufReader::with_capacity(OUT_BUF_SIZE, file);
+            pump(&mut file, out, &filename, interactive, summary).await?;
+        } else {
+            formatter.pipeline.start_input(&filename);
+            let mut formatted = formatter.wrap(file);
+            pump(&mut formatted, out, &filename, interactive, summary).await?;
+            formatter = formatted.into_inner().0;
+        }
+    }
+    Ok(())
+}
+
+/// Writes everything `input` yields into `out`. A read error is reported
+/// in `summary` and ends this input only.
+async fn pump<R, W>(
+    input: &mut R,
+    out: &mut W,
+    path: &str,
+    interactive: bool,
+    summary: &mut Summary
+) -> Result<(), Error>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin
+{
+    loop {
+        let chunk = match input.fill_buf().await {
+            Ok(chunk) => chunk,
+            Err(source) => {
+                summary.report(Error::Read { path: path.to_string(), source });
+                return Ok(());
+            }
+        };
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        let n = chunk.len();
+        out.write_all(chunk).await.map_err(|source| Error::Write { source })?;
+        input.consume(n);
+        if interactive {
+            out.flush().await.map_err(|source| Error::Write { source })?;
+        }
+    }
+}
+
+/// `Formatter` for `AsyncRead` sources.
+pub struct AsyncFormatter {
+    pipeline: Pipeline
+}
+
+impl AsyncFormatter {
+    /// Same as `Formatter::new`, failing on the same options.
+    pub fn new(config: &Config) -> Result<AsyncFormatter, Error> {
+        formatter::lines_only(config, "AsyncFormatter")?;
+        Ok(AsyncFormatter { pipeline: Pipeline::from_config(config) })
+    }
+
+    pub fn with_pipeline(pipeline: Pipeline) -> AsyncFormatter {
+        AsyncFormatter { pipeline }
+    }
+
+    /// Returns a reader yielding the formatted bytes of `reader`.
+    pub fn wrap<R: AsyncRead + Unpin>(self, reader: R) -> AsyncFormatted<R> {
+        AsyncFormatted {
+            pipeline: self.pipeline,
+            reader: BufReader::new(reader),
+            line: Vec::new(),
+            rendered: Vec::new(),
+            pos: 0
+        }
+    }
+}
+
+/// Reader returned by `AsyncFormatter::wrap`.
+pub struct AsyncFormatted<R> {
+    pipeline: Pipeline,
+    reader: BufReader<R>,
+    line: Vec<u8>,
+    rendered: Vec<u8>,
+    pos: usize
+}
+
+impl<R: AsyncRead> AsyncFormatted<R> {
+    /// Gives back the formatter, with its numbering and squeezing state,
+    /// and the wrapped reader. Anything buffered but not read yet is lost.
+    pub fn into_inner(self) -> (AsyncFormatter, R) {
+        (AsyncFormatter { pipeline: self.pipeline }, self.reader.into_inner())
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncFormatted<R> {
+    /// Collects the next line into `self.line`, which keeps partial lines
+    /// while the reader is pending. Resolves to false at the end of input.
+    fn poll_line(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<bool>> {
+        loop {
+            let available = ready!(Pin::new(&mut self.reader).poll_fill_buf(cx))?;
+            if available.is_empty() {
+                return Poll::Ready(Ok(!self.line.is_empty()));
+            }
+
+            let (used, done) = match available.iter().position(|&byte| byte == b'\n') {
+                Some(i) => (i + 1, true),
+                None => (available.len(), false)
+            };
+            self.line.extend_from_slice(&available[..used]);
+            Pin::new(&mut self.reader).consume(used);
+            if done {
+                return Poll::Ready(Ok(true));
+            }
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncBufRead for AsyncFormatted<R> {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        while this.pos == this.rendered.len() {
+            this.rendered.clear();
+            this.pos = 0;
+            if !ready!(this.poll_line(cx))? {
+                break;
+            }
+            if let Some(line) = this.pipeline.process(&this.line) {
+                line.render(&mut this.rendered);
+            }
+            this.line.clear();
+        }
+        Poll::Ready(Ok(&this.rendered[this.pos..]))
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let this = self.get_mut();
+        this.pos = (this.pos + amt).min(this.rendered.len());
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncFormatted<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>
+    ) -> Poll<io::Result<()>> {
+        let available = ready!(self.as_mut().poll_fill_buf(cx))?;
+        let n = available.len().min(buf.remaining());
+        buf.put_slice(&available[..n]);
+        self.consume(n);
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,104 @@
+//! `--color`: ANSI styles for what catr adds around its input, i.e. line
+//! numbers, the markers of `-T`, `-E` and `-v`, and headers. The input
+//! itself is left alone; `--highlight` is what colors it.
+
+use crate::{Line, LineTransform};
+use std::env;
+use std::io::{self, IsTerminal};
+use std::ops::Range;
+
+const NUMBER: &[u8] = b"\x1b[32m";
+const MARKER: &[u8] = b"\x1b[35m";
+const HEADER: &[u8] = b"\x1b[1m";
+const RESET: &[u8] = b"\x1b[0m";
+
+/// When `--color` colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// When the output is the terminal of the process, unless `NO_COLOR`
+    /// is set, or whatever it is when `CLICOLOR_FORCE` is. Only `run`
+    /// knows what its output is; the other entry points don't color.
+    Auto,
+    Always,
+    Never
+}
+
+impl ColorChoice {
+    /// Tells `Auto` apart for output to stdout.
+    pub(crate) fn for_stdout(self) -> ColorChoice {
+        if self != ColorChoice::Auto {
+            return self;
+        }
+        let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        let forced = env::var_os("CLICOLOR_FORCE")
+            .is_some_and(|value| !value.is_empty() && value != "0");
+        if !no_color && (forced || io::stdout().is_terminal()) {
+            ColorChoice::Always
+        } else {
+            ColorChoice::Never
+        }
+    }
+}
+
+/// Appends `text` to `out` in the style of headers.
+pub fn header(text: &str, out: &mut Vec<u8>) {
+    for line in text.split_inclusive('\n') {
+        let (line, newline) = match line.strip_suffix('\n') {
+            Some(line) => (line, "\n"),
+            None => (line, "")
+        };
+        paint(HEADER, line.as_bytes(), out);
+        out.extend_from_slice(newline.as_bytes());
+    }
+}
+
+fn paint(style: &[u8], text: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(style);
+    out.extend_from_slice(text);
+    out.extend_from_slice(RESET);
+}
+
+/// The stage that applies the styles, last in the pipeline. The prefix is
+/// taken for a line number, and `Line::markers` says where the markers
+/// are.
+pub struct Colorize;
+
+impl LineTransform for Colorize {
+    fn apply(&mut self, line: &mut Line) -> bool {
+        if !line.prefix.is_empty() {
+            let mut prefix = Vec::with_capacity(line.prefix.len() + NUMBER.len() + RESET.len());
+            paint(NUMBER, &line.prefix, &mut prefix);
+            line.prefix = prefix;
+        }
+        if line.markers.is_empty() {
+            return true;
+        }
+
+        // Markers next to each other share their escapes.
+        let mut markers: Vec<Range<usize>> = Vec::with_capacity(line.markers.len());
+        for marker in std::mem::take(&mut line.markers) {
+            match markers.last_mut() {
+                Some(last) if last.end == marker.start => last.end = marker.end,
+                _ => markers.push(marker)
+            }
+        }
+
+        let mut content = Vec::with_capacity(line.content.len() + markers.len() * 9);
+        let mut done = 0;
+        for marker in markers {
+            // A custom stage may have moved things under the markers.
+            let Some(text) = line.content.get(marker.clone()) else {
+                continue;
+            };
+            if marker.start < done {
+                continue;
+            }
+            content.extend_from_slice(&line.content[done..marker.start]);
+            paint(MARKER, text, &mut content);
+            done = marker.end;
+        }
+        content.extend_from_slice(&line.content[done..]);
+        line.content = content;
+        true
+    }
+}
@@ -0,0 +1,190 @@
+use crate::Error;
+use std::fs::File;
+use std::io::{self, IsTerminal, Read, Write};
+
+/// Size of the userspace buffer used when the kernel can't copy for us.
+const BUF_SIZE: usize = 128 * 1024;
+
+/// Copies everything left in `input` to stdout without looking at it.
+///
+/// On Linux the data is moved inside the kernel with `copy_file_range`,
+/// `sendfile` or `splice`, whichever the pair of descriptors supports.
+/// Everything else goes through `copy`.
+pub fn copy_to_stdout(input: &mut File, path: &str) -> Result<u64, Error> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    stdout.flush().map_err(|source| Error::Write { source })?;
+
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::fd::AsRawFd;
+
+        let mut copied = 0;
+        if linux::kernel_copy(input.as_raw_fd(), stdout.as_raw_fd(), &mut copied, path)? {
+            return Ok(copied);
+        }
+        Ok(copied + copy(input, &mut stdout, path, input.is_terminal())?)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    copy(input, &mut stdout, path, input.is_terminal())
+}
+
+/// Copies everything left in `input` to `output` through a large buffer,
+/// flushing after every read when `interactive`.
+pub fn copy<R: Read, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    path: &str,
+    interactive: bool
+) -> Result<u64, Error> {
+    let mut buf = vec![0; BUF_SIZE];
+    let mut copied = 0;
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => return Ok(copied),
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(source) => return Err(Error::Read { path: path.to_string(), source })
+        };
+        output.write_all(&buf[..n])
+            .and_then(|()| if interactive { output.flush() } else { Ok(()) })
+            .map_err(|source| Error::Write { source })?;
+        copied += n as u64;
+    }
+}
+
+/// Duplicates the stdin descriptor into a `File` the kernel can copy from.
+#[cfg(unix)]
+pub fn stdin_file() -> io::Result<File> {
+    use std::os::fd::AsFd;
+
+    io::stdin().as_fd().try_clone_to_owned().map(File::from)
+}
+
+#[cfg(windows)]
+pub fn stdin_file() -> io::Result<File> {
+    use std::os::windows::io::AsHandle;
+
+    io::stdin().as_handle().try_clone_to_owned().map(File::from)
+}
+
+/// Device and inode of stdout, when it is a regular file.
+#[cfg(unix)]
+pub fn stdout_file_id() -> Option<(u64, u64)> {
+    use std::os::fd::AsFd;
+    use std::os::unix::fs::MetadataExt;
+
+    let stdout = File::from(io::stdout().as_fd().try_clone_to_owned().ok()?);
+    let meta = stdout.metadata().ok()?;
+    meta.is_file().then(|| (meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn stdout_file_id() -> Option<(u64, u64)> {
+    None
+}
+
+/// GNU cat's check for `input` being the file identified by `output`.
+/// Only unread data counts: an input that is empty or already read up to
+/// its end can't feed on what is appended to it.
+#[cfg(unix)]
+pub fn is_output(input: &mut File, output: (u64, u64)) -> bool {
+    use std::io::Seek;
+    use std::os::unix::fs::MetadataExt;
+
+    let Ok(meta) = input.metadata() else {
+        return false;
+    };
+    (meta.dev(), meta.ino()) == output &&
+        input.stream_position().is_ok_and(|pos| pos < meta.len())
+}
+
+#[cfg(not(unix))]
+pub fn is_output(_input: &mut File, _output: (u64, u64)) -> bool {
+    false
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use crate::Error;
+    use std::io;
+    use std::os::fd::RawFd;
+    use std::ptr;
+
+    /// Largest amount handed to a single syscall.
+    const CHUNK: usize = 1 << 30;
+
+    #[derive(Clone, Copy)]
+    enum Method {
+        CopyFileRange,
+        Sendfile,
+        Splice
+    }
+
+    impl Method {
+        fn call(self, input: RawFd, output: RawFd) -> isize {
+            // SAFETY: both descriptors stay open for the duration of the
+            // call and null offsets make the kernel use the file positions.
+            unsafe {
+                match self {
+                    Method::CopyFileRange => libc::copy_file_range(
+                        input, ptr::null_mut(), output, ptr::null_mut(), CHUNK, 0
+                    ),
+                    Method::Sendfile => libc::sendfile(
+                        output, input, ptr::null_mut(), CHUNK
+                    ),
+                    Method::Splice => libc::splice(
+                        input, ptr::null_mut(), output, ptr::null_mut(), CHUNK,
+                        libc::SPLICE_F_MOVE | libc::SPLICE_F_MORE
+                    )
+                }
+            }
+        }
+    }
+
+    /// Moves the rest of `input` to `output` inside the kernel.
+    ///
+    /// Returns `Ok(false)` when none of the syscalls applies to these
+    /// descriptors; whatever was already copied is counted in `copied` and
+    /// the file positions are left where a userspace copy can pick up.
+    pub fn kernel_copy(
+        input: RawFd,
+        output: RawFd,
+        copied: &mut u64,
+        path: &str
+    ) -> Result<bool, Error> {
+        for method in [Method::CopyFileRange, Method::Sendfile, Method::Splice] {
+            let mut progressed = false;
+            loop {
+                match method.call(input, output) {
+                    // Some pseudo filesystems report 0 without being at EOF,
+                    // so a method only gets to declare EOF after moving data.
+                    0 if progressed => return Ok(true),
+                    0 => break,
+                    n if n > 0 => {
+                        *copied += n as u64;
+                        progressed = true;
+                    }
+                    _ => {
+                        let err = io::Error::last_os_error();
+                        match err.raw_os_error() {
+                            Some(libc::EINTR) => continue,
+                            Some(
+                                libc::EINVAL | libc::EXDEV | libc::ENOSYS |
+                                libc::EBADF | libc::EOPNOTSUPP | libc::EPERM |
+                                libc::ESPIPE
+                            ) => break,
+                            Some(
+                                libc::EPIPE | libc::ENOSPC | libc::EDQUOT |
+                                libc::EFBIG
+                            ) => return Err(Error::Write { source: err }),
+                            _ => return Err(Error::Read { path: path.to_string(), source: err })
+                        }
+                    }
+                }
+            }
+        }
+        Ok(false)
+    }
+}
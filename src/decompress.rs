@@ -0,0 +1,119 @@
+//! `--decompress`: inputs starting with the magic bytes of a compressed
+//! format are decoded on the fly. Every format has its own cargo feature;
+//! a format that wasn't compiled in is still recognized, and reported.
+
+use crate::{Error, Input};
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+/// Enough bytes to tell every supported format apart.
+const MAGIC_LEN: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd
+}
+
+impl Format {
+    /// The format `magic`, the first bytes of an input, belongs to.
+    pub fn detect(magic: &[u8]) -> Option<Format> {
+        match magic {
+            [0x1f, 0x8b, 0x08, ..] => Some(Format::Gzip),
+            [b'B', b'Z', b'h', b'1'..=b'9', ..] => Some(Format::Bzip2),
+            [0xfd, b'7', b'z', b'X', b'Z', 0x00, ..] => Some(Format::Xz),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Format::Zstd),
+            _ => None
+        }
+    }
+
+    /// Also the name of the cargo feature that decodes it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Gzip => "gzip",
+            Format::Bzip2 => "bzip2",
+            Format::Xz => "xz",
+            Format::Zstd => "zstd"
+        }
+    }
+
+    /// Wraps `input`, positioned at the magic bytes, in a decoder. Like
+    /// `zcat`, concatenated streams are decoded one after the other.
+    fn decoder<'a, R: Read + 'a>(self, input: R) -> io::Result<Box<dyn Read + 'a>> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Format::Gzip => Ok(Box::new(flate2::read::MultiGzDecoder::new(input))),
+            #[cfg(feature = "bzip2")]
+            Format::Bzip2 => Ok(Box::new(bzip2::read::MultiBzDecoder::new(input))),
+            #[cfg(feature = "xz")]
+            Format::Xz => Ok(Box::new(xz2::read::XzDecoder::new_multi_decoder(input))),
+            #[cfg(feature = "zstd")]
+            Format::Zstd => Ok(Box::new(zstd::stream::read::Decoder::new(input)?)),
+            #[allow(unreachable_patterns)]
+            format => {
+                drop(input);
+                Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("{} input, but catr was built without the `{0}` feature", format.name())
+                ))
+            }
+        }
+    }
+}
+
+/// Looks at the first bytes of `input` and puts a decoder in front of it
+/// if they are the magic of a compressed format. A file that isn't
+/// compressed is handed back as a file, at the position it was at.
+pub fn open(input: Input, path: &str) -> Result<Input, Error> {
+    let mut file = match input {
+        Input::File(file) => file,
+        Input::Reader(reader) => return wrap(reader, path).map(Input::Reader)
+    };
+    let read = |source| Error::Read { path: path.to_string(), source };
+
+    let mut magic = [0; MAGIC_LEN];
+    let n = read_magic(&mut file, &mut magic).map_err(read)?;
+    let format = Format::detect(&magic[..n]);
+
+    // Pipes can't seek, so what was read is put back in front of them.
+    if file.seek(SeekFrom::Current(-(n as i64))).is_ok() {
+        match format {
+            None => Ok(Input::File(file)),
+            Some(format) => format.decoder(file).map(Input::Reader).map_err(read)
+        }
+    } else {
+        let input = Cursor::new(magic).take(n as u64).chain(file);
+        match format {
+            None => Ok(Input::Reader(Box::new(input))),
+            Some(format) => format.decoder(input).map(Input::Reader).map_err(read)
+        }
+    }
+}
+
+/// `open` for any reader.
+pub fn wrap<'a, R: Read + 'a>(mut input: R, path: &str) -> Result<Box<dyn Read + 'a>, Error> {
+    let read = |source| Error::Read { path: path.to_string(), source };
+
+    let mut magic = [0; MAGIC_LEN];
+    let n = read_magic(&mut input, &mut magic).map_err(read)?;
+    let input = Cursor::new(magic).take(n as u64).chain(input);
+    match Format::detect(&magic[..n]) {
+        None => Ok(Box::new(input)),
+        Some(format) => format.decoder(input).map_err(read)
+    }
+}
+
+/// Fills `magic` unless the input ends first; returns how much was read.
+fn read_magic<R: Read>(input: &mut R, magic: &mut [u8; MAGIC_LEN]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < MAGIC_LEN {
+        match input.read(&mut magic[n..]) {
+            Ok(0) => break,
+            Ok(read) => n += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err)
+        }
+    }
+    Ok(n)
+}
@@ -0,0 +1,196 @@
+//! `--decode` and `--encode`: hex, base64 and base32, decoded from inputs
+//! before anything else looks at them, and encoded into the output after
+//! everything else is done.
+
+use std::io::{self, Read, Write};
+
+/// Text encodings of binary data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Two hex digits a byte; decoding takes either case, encoding gives
+    /// lowercase.
+    Hex,
+    /// RFC 4648 base64, `=` padding optional when decoding.
+    Base64,
+    /// RFC 4648 base32, `=` padding optional when decoding.
+    Base32
+}
+
+/// Encoded lines are wrapped at this width, as `base64` does.
+const WRAP: usize = 76;
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Hex => "hex",
+            Encoding::Base64 => "base64",
+            Encoding::Base32 => "base32"
+        }
+    }
+
+    fn spec(self) -> data_encoding::Encoding {
+        match self {
+            Encoding::Hex => data_encoding::HEXLOWER_PERMISSIVE,
+            Encoding::Base64 => data_encoding::BASE64,
+            Encoding::Base32 => data_encoding::BASE32
+        }
+    }
+
+    /// For input that ends in the middle of a block.
+    fn unpadded(self) -> data_encoding::Encoding {
+        match self {
+            Encoding::Hex => data_encoding::HEXLOWER_PERMISSIVE,
+            Encoding::Base64 => data_encoding::BASE64_NOPAD,
+            Encoding::Base32 => data_encoding::BASE32_NOPAD
+        }
+    }
+
+    /// Bytes encoded as a whole number of characters, with no padding.
+    fn bytes_per_block(self) -> usize {
+        match self {
+            Encoding::Hex => 1,
+            Encoding::Base64 => 3,
+            Encoding::Base32 => 5
+        }
+    }
+
+    /// Characters in a block, padded or not.
+    fn chars_per_block(self) -> usize {
+        match self {
+            Encoding::Hex => 2,
+            Encoding::Base64 => 4,
+            Encoding::Base32 => 8
+        }
+    }
+}
+
+/// Reads what `input` encodes. Whitespace, such as the newlines wrapping
+/// encoded lines, is skipped. Invalid input is an `InvalidData` error.
+pub struct Decoder<R> {
+    input: R,
+    encoding: Encoding,
+    /// Characters read but not decoded yet: less than a block, once a
+    /// read is done.
+    pending: Vec<u8>,
+    decoded: Vec<u8>,
+    pos: usize,
+    done: bool
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(encoding: Encoding, input: R) -> Decoder<R> {
+        Decoder { input, encoding, pending: Vec::new(), decoded: Vec::new(), pos: 0, done: false }
+    }
+
+    /// Decodes the next chunk of input into `self.decoded`.
+    fn refill(&mut self) -> io::Result<()> {
+        let mut chunk = [0; 8 * 1024];
+        let n = loop {
+            match self.input.read(&mut chunk) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                result => break result?
+            }
+        };
+        self.pending.extend(chunk[..n].iter().filter(|byte| !byte.is_ascii_whitespace()));
+
+        let block = self.encoding.chars_per_block();
+        let ready = if n == 0 {
+            self.done = true;
+            self.pending.len()
+        } else {
+            self.pending.len() - self.pending.len() % block
+        };
+        let spec = match ready % block {
+            0 => self.encoding.spec(),
+            _ => self.encoding.unpadded()
+        };
+        self.decoded = spec.decode(&self.pending[..ready]).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid {} input", self.encoding.name())
+            )
+        })?;
+        self.pos = 0;
+        self.pending.drain(..ready);
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.decoded.len() {
+            if self.done {
+                return Ok(0);
+            }
+            self.refill()?;
+        }
+        let n = buf.len().min(self.decoded.len() - self.pos);
+        buf[..n].copy_from_slice(&self.decoded[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Writes what is written into it encoded into `out`, in lines of 76
+/// characters. `finish` writes the end of the last line.
+pub struct Encoder<W> {
+    out: W,
+    encoding: Encoding,
+    /// Bytes that don't make a whole block yet.
+    pending: Vec<u8>,
+    encoded: String,
+    column: usize
+}
+
+impl<W: Write> Encoder<W> {
+    pub fn new(encoding: Encoding, out: W) -> Encoder<W> {
+        Encoder { out, encoding, pending: Vec::new(), encoded: String::new(), column: 0 }
+    }
+
+    /// Encodes what is left, with padding, ends the last line and flushes.
+    pub fn finish(mut self) -> io::Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        self.emit(&pending)?;
+        if self.column > 0 {
+            self.out.write_all(b"\n")?;
+        }
+        self.out.flush()
+    }
+
+    /// Writes `bytes` encoded, wrapping lines.
+    fn emit(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.encoded.clear();
+        self.encoding.spec().encode_append(bytes, &mut self.encoded);
+        let mut rest = self.encoded.as_bytes();
+        while !rest.is_empty() {
+            let (line, tail) = rest.split_at(rest.len().min(WRAP - self.column));
+            self.out.write_all(line)?;
+            self.column += line.len();
+            if self.column == WRAP {
+                self.out.write_all(b"\n")?;
+                self.column = 0;
+            }
+            rest = tail;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        let block = self.encoding.bytes_per_block();
+        let ready = self.pending.len() - self.pending.len() % block;
+        let pending = std::mem::take(&mut self.pending);
+        let result = self.emit(&pending[..ready]);
+        self.pending = pending;
+        self.pending.drain(..ready);
+        result.map(|()| buf.len())
+    }
+
+    /// Only flushes whole blocks: what doesn't make one can't be encoded
+    /// until more comes, or `finish`.
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+use std::io;
+
+/// Everything that can go wrong while concatenating files.
+///
+/// Failures tied to one input name the path they happened on (`-` for
+/// stdin); they are reported and the remaining files are still processed.
+/// A `Write` failure stops the whole run, as does a bad `Pattern` or an
+/// `Unsupported` option.
+#[derive(Debug)]
+pub enum Error {
+    Open { path: String, source: io::Error },
+    Read { path: String, source: io::Error },
+    Write { source: io::Error },
+    IsDirectory { path: String },
+    InputIsOutput { path: String },
+    /// An `--include` or `--exclude` glob that doesn't parse.
+    Pattern { pattern: String, message: String },
+    /// An option set in the `Config` given to an entry point that can't
+    /// honour it, e.g. `--follow` for `run_with`.
+    Unsupported { option: &'static str, entry: &'static str }
+}
+
+impl Error {
+    /// The input this error is about, if any.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Error::Open { path, .. } |
+            Error::Read { path, .. } |
+            Error::IsDirectory { path } |
+            Error::InputIsOutput { path } => Some(path),
+            Error::Write { .. } |
+            Error::Pattern { .. } |
+            Error::Unsupported { .. } => None
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Open { path, source } |
+            Error::Read { path, source } => write!(f, "{path}: {source}"),
+            Error::Write { source } => write!(f, "write error: {source}"),
+            Error::IsDirectory { path } => write!(f, "{path}: Is a directory"),
+            Error::InputIsOutput { path } => write!(f, "{path}: input file is output file"),
+            Error::Pattern { pattern, message } => write!(f, "invalid pattern '{pattern}': {message}"),
+            Error::Unsupported { option, entry } => write!(f, "{option} is not supported by {entry}")
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Open { source, .. } |
+            Error::Read { source, .. } |
+            Error::Write { source } => Some(source),
+            Error::IsDirectory { .. } |
+            Error::InputIsOutput { .. } |
+            Error::Pattern { .. } |
+            Error::Unsupported { .. } => None
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! `-f`: waiting for the last input to change once it has been read.
+
+use std::fs::{self, File, Metadata};
+use std::io::{self, Seek};
+use std::time::Duration;
+
+/// What `-f` keeps reading, as with `tail`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowMode {
+    /// The file that was opened, wherever it gets moved.
+    Descriptor,
+    /// Whatever file has the name, reopened when it gets replaced, e.g. by
+    /// log rotation.
+    Name
+}
+
+/// Longest wait between two looks at the file.
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// What `Follower::wait` found.
+pub enum Change {
+    /// There is more to read.
+    Grown,
+    /// The file got shorter than what was read, so reading starts over.
+    Truncated,
+    /// Another file has the name now; read this one once done with the
+    /// old one.
+    Replaced(File)
+}
+
+pub struct Follower {
+    path: String,
+    mode: FollowMode,
+    /// Shares its position with the file being read.
+    file: File,
+    id: Option<(u64, u64)>,
+    watch: Watch
+}
+
+impl Follower {
+    /// Follows `file`, opened from `path`, if it is a regular file. Stdin
+    /// is always followed by descriptor, having no name.
+    pub fn new(file: &File, path: &str, mode: FollowMode) -> Option<Follower> {
+        let meta = file.metadata().ok().filter(Metadata::is_file)?;
+        let mode = if path == "-" { FollowMode::Descriptor } else { mode };
+        Some(Follower {
+            path: path.to_string(),
+            mode,
+            file: file.try_clone().ok()?,
+            id: file_id(&meta),
+            watch: Watch::new(path)
+        })
+    }
+
+    /// Another handle on the file being followed, sharing its position.
+    pub fn file(&self) -> io::Result<File> {
+        self.file.try_clone()
+    }
+
+    /// Blocks until the file has changed in a way worth reading it again.
+    /// The reader must be at the end of the file.
+    pub fn wait(&mut self) -> io::Result<Change> {
+        loop {
+            if let Some(file) = self.replacement() {
+                self.watch = Watch::new(&self.path);
+                return Ok(Change::Replaced(file));
+            }
+
+            let len = self.file.metadata()?.len();
+            let pos = self.file.stream_position()?;
+            if len > pos {
+                return Ok(Change::Grown);
+            }
+            if len < pos {
+                return Ok(Change::Truncated);
+            }
+            self.watch.wait();
+        }
+    }
+
+    /// The file now at the path in `FollowMode::Name`, if it isn't the one
+    /// being read. While no file has the name, the old one is kept.
+    fn replacement(&mut self) -> Option<File> {
+        if self.mode != FollowMode::Name {
+            return None;
+        }
+        let meta = fs::metadata(&self.path).ok()?;
+        if !meta.is_file() || file_id(&meta) == self.id {
+            return None;
+        }
+
+        let file = File::open(&self.path).ok()?;
+        self.id = file_id(&file.metadata().ok()?);
+        self.file = file.try_clone().ok()?;
+        Some(file)
+    }
+}
+
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// How to wait for a change: inotify where there is one, sleeping
+/// otherwise.
+enum Watch {
+    #[cfg(target_os = "linux")]
+    Inotify(std::os::fd::OwnedFd),
+    Poll
+}
+
+impl Watch {
+    #[cfg(target_os = "linux")]
+    fn new(path: &str) -> Watch {
+        inotify::watch(path).map_or(Watch::Poll, Watch::Inotify)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn new(_path: &str) -> Watch {
+        Watch::Poll
+    }
+
+    /// Returns when the file may have changed, or after `INTERVAL`: files
+    /// that replace the one watched don't send any event.
+    fn wait(&self) {
+        match self {
+            #[cfg(target_os = "linux")]
+            Watch::Inotify(fd) => inotify::wait(fd, INTERVAL),
+            Watch::Poll => std::thread::sleep(INTERVAL)
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+    use std::ffi::CString;
+    use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+    use std::time::Duration;
+
+    /// An inotify instance watching `path` for writes, truncation, moves
+    /// and deletion, or `None` when inotify isn't available.
+    pub fn watch(path: &str) -> Option<OwnedFd> {
+        let path = CString::new(if path == "-" { "/dev/stdin" } else { path }).ok()?;
+
+        // SAFETY: no pointers involved; the descriptor returned is owned
+        // by nothing else.
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mask = libc::IN_MODIFY | libc::IN_ATTRIB | libc::IN_MOVE_SELF | libc::IN_DELETE_SELF;
+        // SAFETY: `path` is a valid C string for the duration of the call.
+        let wd = unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) };
+        (wd >= 0).then_some(fd)
+    }
+
+    /// Waits for events on `fd` for at most `timeout`, then discards them:
+    /// they only mean the file is worth another look.
+    pub fn wait(fd: &OwnedFd, timeout: Duration) {
+        let mut pollfd = libc::pollfd { fd: fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        let mut events = [0u8; 4096];
+        // SAFETY: `pollfd` and `events` outlive the calls using them, and
+        // `fd` is open.
+        unsafe {
+            libc::poll(&mut pollfd, 1, timeout.as_millis() as libc::c_int);
+            while libc::read(fd.as_raw_fd(), events.as_mut_ptr().cast(), events.len()) > 0 {}
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{unsupported, Config, Error, Pipeline};
+use std::io::{self, BufRead, BufReader, Read};
+
+/// cat's formatting as a reader adapter, for rendering without going
+/// through stdout:
+///
+/// ```no_run
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::io::Read;
+///
+/// let config = catr::Config::try_from_args(["catr", "-n", "-A"])?;
+/// let mut rendered = String::new();
+/// catr::Formatter::new(&config)?
+///     .wrap(catr::open("app.log")?)
+///     .read_to_string(&mut rendered)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct Formatter {
+    pipeline: Pipeline
+}
+
+impl Formatter {
+    /// Formats lines the way `run` would with `config`. `config.files` is
+    /// not used: the input is whatever gets wrapped. Options about more
+    /// than lines, e.g. `lines` or `decompress`, fail with
+    /// `Error::Unsupported`.
+    pub fn new(config: &Config) -> Result<Formatter, Error> {
+        lines_only(config, "Formatter")?;
+        Ok(Formatter { pipeline: Pipeline::from_config(config) })
+    }
+
+    pub fn with_pipeline(pipeline: Pipeline) -> Formatter {
+        Formatter { pipeline }
+    }
+
+    /// Returns a reader yielding the formatted bytes of `reader`.
+    ///
+    /// To number several inputs as one stream, like `run` does, wrap them
+    /// chained together with `Read::chain`.
+    pub fn wrap<R: Read>(self, reader: R) -> Formatted<R> {
+        Formatted {
+            pipeline: self.pipeline,
+            reader: BufReader::new(reader),
+            line: Vec::new(),
+            rendered: Vec::new(),
+            pos: 0
+        }
+    }
+}
+
+/// Fails on the options of `config` that are about more than the lines
+/// of one input, which `entry` formats.
+pub(crate) fn lines_only(config: &Config, entry: &'static str) -> Result<(), Error> {
+    unsupported(entry, &[
+        ("--decompress", config.decompress),
+        ("--headers", config.headers.is_some()),
+        ("--lines", config.lines.is_some()),
+        ("--bytes", config.bytes.is_some()),
+        ("--hex", config.hex),
+        ("--decode", config.decode.is_some()),
+        ("--encode", config.encode.is_some()),
+        ("--follow", config.follow.is_some())
+    ])
+}
+
+/// Reader returned by `Formatter::wrap`.
+pub struct Formatted<R> {
+    pipeline: Pipeline,
+    reader: BufReader<R>,
+    line: Vec<u8>,
+    rendered: Vec<u8>,
+    pos: usize
+}
+
+impl<R> Formatted<R> {
+    /// Gives back the formatter, with its numbering and squeezing state,
+    /// and the wrapped reader. Anything buffered but not read yet is lost.
+    pub fn into_inner(self) -> (Formatter, R) {
+        (Formatter { pipeline: self.pipeline }, self.reader.into_inner())
+    }
+}
+
+impl<R: Read> BufRead for Formatted<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        // A custom stage may leave a line with nothing to print, which
+        // must not pass for the end of input.
+        while self.pos == self.rendered.len() {
+            self.rendered.clear();
+            self.pos = 0;
+            let more = next_line(
+                &mut self.pipeline, &mut self.reader, &mut self.line, &mut self.rendered
+            )?;
+            if !more {
+                break;
+            }
+        }
+        Ok(&self.rendered[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.rendered.len());
+    }
+}
+
+impl<R: Read> Read for Formatted<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+/// Reads lines from `input` until one makes it through `pipeline`, and
+/// appends it as printed to `rendered`. Returns false at the end of
+/// `input`. `line` is scratch space kept by the caller between calls.
+pub(crate) fn next_line<R: BufRead>(
+    pipeline: &mut Pipeline,
+    input: &mut R,
+    line: &mut Vec<u8>,
+    rendered: &mut Vec<u8>
+) -> io::Result<bool> {
+    loop {
+        line.clear();
+        if input.read_until(b'\n', line)? == 0 {
+            return Ok(false);
+        }
+        if let Some(line) = pipeline.process(line) {
+            line.render(rendered);
+            return Ok(true);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! `--headers`: the name of each input printed before its contents.
+
+use crate::{color, ColorChoice, Config};
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How `--headers` sets inputs apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderStyle {
+    /// `==> name <==`, like `head` and `tail`.
+    Head,
+    /// The name in a box of box-drawing characters.
+    Box
+}
+
+/// Renders the headers of a run. Every header but the first starts with
+/// an empty line, which also ends a last line left without a newline.
+pub struct Headers {
+    style: Option<HeaderStyle>,
+    details: bool,
+    color: bool,
+    first: bool
+}
+
+impl Headers {
+    pub fn new(config: &Config) -> Headers {
+        Headers {
+            style: config.headers,
+            details: config.header_details,
+            color: config.color == ColorChoice::Always,
+            first: true
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.style.is_some()
+    }
+
+    /// Appends the header of `name` to `out`.
+    pub fn render(&mut self, name: &str, out: &mut Vec<u8>) {
+        let Some(style) = self.style else {
+            return;
+        };
+        if !std::mem::replace(&mut self.first, false) {
+            out.push(b'\n');
+        }
+
+        let mut label = match name {
+            "-" => "standard input".to_string(),
+            _ => name.to_string()
+        };
+        // Only regular files have a size and time worth showing.
+        let meta = if self.details && name != "-" {
+            fs::metadata(name).ok().filter(|meta| meta.is_file())
+        } else {
+            None
+        };
+        if let Some(meta) = meta {
+            label += &format!(" ({} bytes", meta.len());
+            if let Some(time) = meta.modified().ok().and_then(format_time) {
+                label += &format!(", modified {time}");
+            }
+            label.push(')');
+        }
+
+        let header = match style {
+            HeaderStyle::Head => format!("==> {label} <==\n"),
+            HeaderStyle::Box => {
+                let rule = "─".repeat(label.chars().count() + 2);
+                format!("┌{rule}┐\n│ {label} │\n└{rule}┘\n")
+            }
+        };
+        if self.color {
+            color::header(&header, out);
+        } else {
+            out.extend_from_slice(header.as_bytes());
+        }
+    }
+}
+
+/// `time` in UTC, as in `2022-11-13T08:05:00Z`; `None` before 1970.
+fn format_time(time: SystemTime) -> Option<String> {
+    let secs = time.duration_since(UNIX_EPOCH).ok()?.as_secs();
+    let (year, month, day) = civil_from_days(secs / 86400);
+    let secs = secs % 86400;
+    Some(format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600, secs % 3600 / 60, secs % 60
+    ))
+}
+
+/// The date `days` after 1970-01-01, after Howard Hinnant's
+/// `civil_from_days`.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (
+        day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096
+    ) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as u64;
+    (year, month, day)
+}
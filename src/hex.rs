@@ -0,0 +1,100 @@
+//! `--hex`: inputs shown the way `hexdump -C` shows them, sixteen bytes
+//! to a row with their offset and their ASCII text. A row like the one
+//! before is left out, and a `*` marks where rows were.
+
+use crate::{ByteRange, Error};
+use std::io::{self, Read, Write};
+
+const ROW: usize = 16;
+const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Writes the dump of `input`, or of the part `range` selects, into `out`.
+/// Offsets are those in `input`. Flushes after every row when
+/// `interactive`.
+pub fn dump<R: Read, W: Write>(
+    input: &mut R,
+    range: Option<ByteRange>,
+    path: &str,
+    out: &mut W,
+    interactive: bool
+) -> Result<(), Error> {
+    let read = |source| Error::Read { path: path.to_string(), source };
+    let write = |source| Error::Write { source };
+
+    let range = range.unwrap_or(ByteRange { offset: 0, len: None });
+    let skipped = io::copy(&mut input.take(range.offset), &mut io::sink()).map_err(read)?;
+    let mut input = input.take(range.len.unwrap_or(u64::MAX));
+
+    let mut offset = skipped;
+    let mut row = [0; ROW];
+    let mut last = None;
+    let mut starred = false;
+    let mut line = Vec::with_capacity(80);
+    loop {
+        let n = fill(&mut input, &mut row).map_err(read)?;
+        if n == 0 {
+            break;
+        }
+
+        if n == ROW && last == Some(row) {
+            if !starred {
+                out.write_all(b"*\n").map_err(write)?;
+                starred = true;
+            }
+        } else {
+            line.clear();
+            render(offset, &row[..n], &mut line);
+            out.write_all(&line).map_err(write)?;
+            starred = false;
+        }
+        last = (n == ROW).then_some(row);
+        offset += n as u64;
+        if interactive {
+            out.flush().map_err(write)?;
+        }
+    }
+
+    if offset > skipped {
+        out.write_all(format!("{offset:08x}\n").as_bytes()).map_err(write)?;
+    }
+    Ok(())
+}
+
+/// Reads into `row` until it is full or `input` ends.
+fn fill<R: Read>(input: &mut R, row: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < row.len() {
+        match input.read(&mut row[n..]) {
+            Ok(0) => break,
+            Ok(read) => n += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err)
+        }
+    }
+    Ok(n)
+}
+
+/// One row: `00000010  6f 67 2e 0a ...  |og..|`. The hex of a short last
+/// row is padded so that its text lines up with the rows above.
+fn render(offset: u64, bytes: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(format!("{offset:08x} ").as_bytes());
+    for i in 0..ROW {
+        if i % 8 == 0 {
+            out.push(b' ');
+        }
+        match bytes.get(i) {
+            Some(&byte) => out.extend_from_slice(&[
+                DIGITS[usize::from(byte >> 4)],
+                DIGITS[usize::from(byte & 0xf)],
+                b' '
+            ]),
+            None => out.extend_from_slice(b"   ")
+        }
+    }
+    out.extend_from_slice(b" |");
+    out.extend(bytes.iter().map(|&byte| match byte {
+        0x20..=0x7e => byte,
+        _ => b'.'
+    }));
+    out.extend_from_slice(b"|\n");
+}
@@ -0,0 +1,110 @@
+//! `--highlight`: colors the text of lines as source code, with syntect's
+//! bundled syntaxes and its `base16-ocean.dark` theme. The stage runs last,
+//! so line numbers and other prefixes are left uncolored.
+
+use crate::{Line, LineTransform};
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::util::as_24_bit_terminal_escaped;
+
+const THEME: &str = "base16-ocean.dark";
+const RESET: &[u8] = b"\x1b[0m";
+
+/// Syntaxes and the theme, loaded once per process: building them takes
+/// far longer than highlighting a typical file.
+fn assets() -> &'static (SyntaxSet, Theme) {
+    static ASSETS: OnceLock<(SyntaxSet, Theme)> = OnceLock::new();
+    ASSETS.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults();
+        let theme = themes.themes.remove(THEME).unwrap_or_default();
+        (SyntaxSet::load_defaults_newlines(), theme)
+    })
+}
+
+/// Checks `language` for `--highlight=LANG`: `auto`, or a syntax name or
+/// file extension syntect knows.
+pub fn parse_language(language: &str) -> Result<String, String> {
+    if language == "auto" || assets().0.find_syntax_by_token(language).is_some() {
+        Ok(language.to_string())
+    } else {
+        Err(format!("no syntax for '{language}'"))
+    }
+}
+
+/// The highlighting stage. Inputs get their language from the extension
+/// or name of the file, or else from their first line, e.g. a shebang;
+/// nothing is colored when neither tells.
+pub struct Highlight {
+    language: Option<&'static SyntaxReference>,
+    name: String,
+    lines: Option<HighlightLines<'static>>,
+    started: bool
+}
+
+impl Highlight {
+    /// Colors every input as `language`, a syntax name or file extension,
+    /// or detects it per input when `language` is `auto` or unknown.
+    pub fn new(language: &str) -> Highlight {
+        let language = match language {
+            "auto" => None,
+            language => assets().0.find_syntax_by_token(language)
+        };
+        Highlight { language, name: String::new(), lines: None, started: false }
+    }
+
+    fn detect(&self, first_line: &str) -> Option<&'static SyntaxReference> {
+        let syntaxes = &assets().0;
+        let path = Path::new(&self.name);
+        let by_name = |part: Option<&std::ffi::OsStr>| {
+            part.and_then(|part| part.to_str())
+                .and_then(|part| syntaxes.find_syntax_by_extension(part))
+        };
+        self.language
+            .or_else(|| by_name(path.file_name()))
+            .or_else(|| by_name(path.extension()))
+            .or_else(|| syntaxes.find_syntax_by_first_line(first_line))
+            .filter(|syntax| syntax.name != syntaxes.find_syntax_plain_text().name)
+    }
+}
+
+impl LineTransform for Highlight {
+    fn start_input(&mut self, name: &str) {
+        self.name = name.to_string();
+        self.lines = None;
+        self.started = false;
+    }
+
+    fn apply(&mut self, line: &mut Line) -> bool {
+        let text = String::from_utf8_lossy(&line.content);
+        if !self.started {
+            self.started = true;
+            self.lines = self.detect(&text)
+                .map(|syntax| HighlightLines::new(syntax, &assets().1));
+        }
+        let Some(lines) = &mut self.lines else {
+            return true;
+        };
+
+        // Syntaxes expect lines with their newline, even when it isn't
+        // printed; the state has to go through every line regardless.
+        let text = format!("{text}\n");
+        let Ok(ranges) = lines.highlight_line(&text, &assets().0) else {
+            return true;
+        };
+        if std::str::from_utf8(&line.content).is_err() || line.content.is_empty() {
+            return true;
+        }
+        let ranges: Vec<_> = ranges.into_iter()
+            .map(|(style, text)| (style, text.strip_suffix('\n').unwrap_or(text)))
+            .filter(|(_, text)| !text.is_empty())
+            .collect();
+        line.content = as_24_bit_terminal_escaped(&ranges, false).into_bytes();
+        line.content.extend_from_slice(RESET);
+        // The escapes moved whatever markers there were.
+        line.markers.clear();
+        true
+    }
+}
@@ -1,91 +1,761 @@
-use clap::{arg, command};
-use std::char;
-use std::error::Error;
+use clap::error::ErrorKind;
+use clap::{arg, command, value_parser, ArgAction, ArgMatches, Command};
+use std::ffi::OsString;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::str::FromStr;
 
-type RunResult<T> = Result<T, Box<dyn Error>>;
+mod archive;
+mod color;
+mod copy;
+mod decompress;
+mod encoding;
+mod error;
+mod follow;
+mod formatter;
+mod header;
+mod hex;
+mod pager;
+#[cfg(feature = "highlight")]
+mod highlight;
+mod select;
+mod walk;
+pub mod transform;
 
-#[derive(Debug)]
+pub use color::{ColorChoice, Colorize};
+pub use encoding::Encoding;
+pub use error::Error;
+pub use follow::FollowMode;
+pub use formatter::{Formatted, Formatter};
+pub use header::HeaderStyle;
+pub use pager::PagingMode;
+#[cfg(feature = "highlight")]
+pub use highlight::Highlight;
+pub use select::{ByteRange, LineRange, ParseRangeError};
+
+use follow::{Change, Follower};
+use header::Headers;
+
+#[cfg(feature = "async")]
+mod asynchronous;
+
+#[cfg(feature = "async")]
+pub use asynchronous::{
+    open_async, run_async, run_async_to, AsyncFormatted, AsyncFormatter
+};
+pub use transform::{Line, LineTransform, NumberFormat, NumberStyle, Pipeline, Section};
+
+type RunResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+/// Capacity of the buffer `run` puts in front of stdout.
+const OUT_BUF_SIZE: usize = 64 * 1024;
+
+/// What to read and how to render it.
+///
+/// Every option is off in `Config::default()`, which reads stdin; set the
+/// fields you need and leave the rest to `..Config::default()`.
+#[derive(Debug, Clone)]
 pub struct Config {
-    files: Vec<String>,
-    number_lines: bool,
-    number_nonblank_lines: bool,
-    show_tabs: bool,
-    show_ends: bool,
-    show_nonprinting: bool,
-    squeeze_blank: bool
-}
-
-pub fn run(config: Config) -> RunResult<()> {
-    for filename in &config.files {
-        let file = match open(filename) {
-            Ok(file) => file,            
+    /// Paths to read in order, `-` meaning stdin. Only used by `run` and
+    /// `run_to`; `run_with` takes its sources directly.
+    pub files: Vec<String>,
+    /// `-n`: number every output line.
+    pub number_lines: bool,
+    /// `-b`: number non-blank output lines; wins over `number_lines`.
+    pub number_nonblank_lines: bool,
+    /// `--number-width` and the like: how line numbers look.
+    pub number_style: NumberStyle,
+    /// `--number-sections`: split the input into nl's logical pages with
+    /// lines made of this delimiter, and only number their bodies.
+    pub number_sections: Option<String>,
+    /// `-T`: display TAB characters as `^I`.
+    pub show_tabs: bool,
+    /// `-E`: display `$` at the end of each line.
+    pub show_ends: bool,
+    /// `-v`: use `^` and `M-` notation for control and high bytes.
+    pub show_nonprinting: bool,
+    /// `-s`: keep at most this many blank lines in a row.
+    pub squeeze_blank: Option<usize>,
+    /// `--squeeze-whitespace`: count whitespace-only lines as blank when
+    /// squeezing.
+    pub squeeze_whitespace: bool,
+    /// `-z`: decode inputs compressed with gzip, bzip2, xz or zstd,
+    /// recognized by their first bytes. `run_async` fails with
+    /// `Error::Unsupported` when it is set, as it does for the other
+    /// options that change how inputs are read.
+    pub decompress: bool,
+    /// `--archive-member`: read `ARCHIVE:MEMBER` files as a member of a tar
+    /// or zip archive even when a file has that very name. Without it,
+    /// members are only looked for when no such file exists. Not supported
+    /// by `run_async`.
+    pub archive_members: bool,
+    /// `-r`: read the files under directories, in name order, leaving out
+    /// what `.gitignore` files and the like ignore, and `.git` directories.
+    /// Hidden files are read.
+    pub recursive: bool,
+    /// `--include`: with `recursive`, only read files matching one of
+    /// these globs.
+    pub include: Vec<String>,
+    /// `--exclude`: with `recursive`, skip files and directories matching
+    /// any of these globs.
+    pub exclude: Vec<String>,
+    /// `--headers`: print the name of each input before it, unless there
+    /// is a single input.
+    pub headers: Option<HeaderStyle>,
+    /// `--header-details`: add the size and modification time of files to
+    /// their header.
+    pub header_details: bool,
+    /// `--lines`: only print these lines of each input. Line numbers
+    /// start over with every input, so that they are those of the lines
+    /// in it. Not supported by `run_async`.
+    pub lines: Option<LineRange>,
+    /// `--bytes`: only print these bytes of each input when `lines` isn't
+    /// set, numbering lines like `lines` does. Not supported by
+    /// `run_async`.
+    pub bytes: Option<ByteRange>,
+    /// `-f`: once the last input is read, keep printing what gets
+    /// appended to it, if it is a regular file. The run then only ends
+    /// with an error. Only supported by `run` and `run_to`; the other
+    /// entry points fail with `Error::Unsupported` when it is set.
+    pub follow: Option<FollowMode>,
+    /// `--highlight`: color the text of lines as this language, a syntax
+    /// name or file extension, or `auto` to tell it from each input's name
+    /// or first line. Only used with the `highlight` feature.
+    pub highlight: Option<String>,
+    /// `--color`: style line numbers, the markers of `show_tabs`,
+    /// `show_ends` and `show_nonprinting`, and headers with ANSI escapes.
+    /// `Auto` is the default on the command line, and only colors what
+    /// `run` writes.
+    pub color: ColorChoice,
+    /// `--paging`: have `run` write into `$PAGER`, or `less -R`. Only
+    /// used by `run`.
+    pub paging: Option<PagingMode>,
+    /// `--hex`: show inputs as `hexdump -C` does instead of formatting
+    /// their lines, only the part `bytes` selects if set. Not supported by
+    /// `run_async`.
+    pub hex: bool,
+    /// `--decode`: read inputs as text in this encoding and use what it
+    /// encodes, before decompressing it with `decompress`. Not supported by
+    /// `run_async`.
+    pub decode: Option<Encoding>,
+    /// `--encode`: write the output in this encoding, in lines of 76
+    /// characters. Not supported by `run_async`.
+    pub encode: Option<Encoding>
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            files: vec!["-".to_string()],
+            number_lines: false,
+            number_nonblank_lines: false,
+            number_style: NumberStyle::default(),
+            number_sections: None,
+            show_tabs: false,
+            show_ends: false,
+            show_nonprinting: false,
+            squeeze_blank: None,
+            squeeze_whitespace: false,
+            decompress: false,
+            archive_members: false,
+            recursive: false,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            headers: None,
+            header_details: false,
+            lines: None,
+            bytes: None,
+            follow: None,
+            highlight: None,
+            color: ColorChoice::Never,
+            paging: None,
+            hex: false,
+            decode: None,
+            encode: None
+        }
+    }
+}
+
+impl Config {
+    /// Parses `args` the way `get_args` parses the process arguments,
+    /// returning clap's error instead of exiting.
+    pub fn try_from_args<I, T>(args: I) -> RunResult<Config>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone
+    {
+        let matches = cli().try_get_matches_from(args)?;
+        check_features(&matches)?;
+        Ok(Config::from_matches(&matches))
+    }
+
+    fn from_matches(matches: &ArgMatches) -> Config {
+        let files = matches.get_many::<String>("files")
+            .unwrap()
+            .map(String::clone)
+            .collect();
+        
+        let globs = |id| matches.get_many::<String>(id)
+            .map(|globs| globs.cloned().collect())
+            .unwrap_or_default();
+        let number_nonblank_lines = matches.get_flag("number_nonblank");
+        let nl_style = NUMBER_OPTIONS.iter().any(|&id| matches.contains_id(id));
+        let defaults = NumberStyle::default();
+        let number_style = NumberStyle {
+            width: matches.get_one::<u64>("number_width")
+                .map_or(defaults.width, |&width| width as usize),
+            separator: matches.get_one::<String>("number_separator")
+                .cloned()
+                .unwrap_or(defaults.separator),
+            format: match matches.get_one::<String>("number_format").map(String::as_str) {
+                Some("ln") => NumberFormat::Left,
+                Some("rz") => NumberFormat::Zeros,
+                _ => NumberFormat::Right
+            },
+            start: matches.get_one::<i64>("number_start").copied().unwrap_or(defaults.start),
+            increment: matches.get_one::<i64>("number_increment")
+                .copied()
+                .unwrap_or(defaults.increment)
+        };
+        let encoding = |id| matches.get_one::<String>(id).map(|name| match name.as_str() {
+            "hex" => Encoding::Hex,
+            "base32" => Encoding::Base32,
+            _ => Encoding::Base64
+        });
+        let squeeze_whitespace = matches.get_flag("squeeze_whitespace");
+        let header_details = matches.get_flag("header_details");
+        let (show_all, vt, ve) = (
+            matches.get_flag("show_all"),
+            matches.get_flag("vT"),
+            matches.get_flag("vE")
+        );
+
+        Config {
+            files,
+            number_lines: matches.get_flag("number") || nl_style && !number_nonblank_lines,
+            number_nonblank_lines,
+            number_style,
+            number_sections: matches.get_one::<String>("number_sections").cloned(),
+            show_tabs:
+                matches.get_flag("show_tabs") ||
+                show_all || vt,
+            show_ends:
+                matches.get_flag("show_ends") ||
+                show_all || ve,
+            show_nonprinting:
+                matches.get_flag("show_nonprinting") ||
+                show_all || ve || vt,
+            squeeze_blank: matches.get_one::<u64>("squeeze_blank")
+                .map(|&n| n as usize)
+                .or(squeeze_whitespace.then_some(1)),
+            squeeze_whitespace,
+            decompress: matches.get_flag("decompress"),
+            archive_members: matches.get_flag("archive_member"),
+            recursive: matches.get_flag("recursive"),
+            include: globs("include"),
+            exclude: globs("exclude"),
+            headers: matches.get_one::<String>("headers")
+                .map(|style| match style.as_str() {
+                    "box" => HeaderStyle::Box,
+                    _ => HeaderStyle::Head
+                })
+                .or(header_details.then_some(HeaderStyle::Head)),
+            header_details,
+            lines: matches.get_one::<LineRange>("lines").copied(),
+            bytes: matches.get_one::<ByteRange>("bytes").copied(),
+            follow: matches.get_one::<String>("follow").map(|mode| match mode.as_str() {
+                "name" => FollowMode::Name,
+                _ => FollowMode::Descriptor
+            }),
+            highlight: matches.get_one::<String>("highlight").cloned(),
+            color: match matches.get_one::<String>("color").map(String::as_str) {
+                Some("always") => ColorChoice::Always,
+                Some("never") => ColorChoice::Never,
+                _ => ColorChoice::Auto
+            },
+            paging: match matches.get_one::<String>("paging").map(String::as_str) {
+                Some("auto") => Some(PagingMode::Auto),
+                Some("always") => Some(PagingMode::Always),
+                _ => None
+            },
+            hex: matches.get_flag("hex"),
+            decode: encoding("decode"),
+            encode: encoding("encode")
+        }
+    }
+}
+
+/// How the output side of a run ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteOutcome {
+    /// Everything was written.
+    #[default]
+    Completed,
+    /// The consumer went away (broken pipe), so the run stopped early.
+    /// This isn't an error: `catr big.log | head` is meant to end this way.
+    Closed
+}
+
+/// What happened during a run that wasn't stopped by an `Error`.
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// Inputs that couldn't be copied, in the order they were reported.
+    pub failures: Vec<Error>,
+    pub outcome: WriteOutcome,
+    /// Whether failures and notices also go to stderr as they happen, as
+    /// they do for `run`. The other entry points leave that to the caller.
+    verbose: bool
+}
+
+impl Summary {
+    /// True when every input was copied in full, i.e. when cat exits 0.
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty() && self.outcome == WriteOutcome::Completed
+    }
+
+    /// The summary of a run on stdout, which tells on stderr what goes
+    /// wrong.
+    fn verbose() -> Summary {
+        Summary { verbose: true, ..Summary::default() }
+    }
+
+    fn report(&mut self, err: Error) {
+        if self.verbose {
+            eprintln!("catr: {err}");
+        }
+        self.failures.push(err);
+    }
+
+    /// Tells about something that isn't a failure, e.g. a followed file
+    /// being truncated.
+    fn notice(&self, path: &str, message: &str) {
+        if self.verbose {
+            eprintln!("catr: {path}: {message}");
+        }
+    }
+
+    /// Reports a failure confined to one input, passing write errors on.
+    fn absorb<T>(&mut self, result: Result<T, Error>) -> Result<(), Error> {
+        match result {
+            Ok(_) => Ok(()),
+            Err(err @ Error::Write { .. }) => Err(err),
+            Err(err) => {
+                self.report(err);
+                Ok(())
+            }
+        }
+    }
+
+    /// Folds the result of a run into the summary, turning a broken pipe
+    /// into `WriteOutcome::Closed`.
+    fn finish(mut self, result: Result<(), Error>) -> Result<Summary, Error> {
+        match result {
+            Ok(()) => Ok(self),
+            Err(Error::Write { source }) if source.kind() == io::ErrorKind::BrokenPipe => {
+                self.outcome = WriteOutcome::Closed;
+                Ok(self)
+            }
+            Err(err) => Err(err)
+        }
+    }
+}
+
+pub fn run(config: Config) -> Result<Summary, Error> {
+    let config = Config { color: config.color.for_stdout(), ..config };
+    let paging = config.paging
+        .filter(|&mode| mode == PagingMode::Always || io::stdout().is_terminal());
+    if let Some(mode) = paging {
+        return pager::run(&config, mode);
+    }
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::with_capacity(OUT_BUF_SIZE, stdout.lock());
+    let mut summary = Summary::verbose();
+    let result = cat_encoded(&config, &mut out, true, &mut summary)
+        .and_then(|()| out.flush().map_err(|source| Error::Write { source }));
+    summary.finish(result)
+}
+
+/// Same as `run`, but writes into `out` instead of stdout. Failures are
+/// left in the summary rather than printed to stderr.
+pub fn run_to<W: Write>(config: &Config, mut out: W) -> Result<Summary, Error> {
+    let mut summary = Summary::default();
+    let result = cat_encoded(config, &mut out, false, &mut summary)
+        .and_then(|()| out.flush().map_err(|source| Error::Write { source }));
+    summary.finish(result)
+}
+
+/// Same as `run_to`, but reads `sources` instead of `config.files`.
+/// Each source comes with the name its errors are reported under.
+pub fn run_with<I, N, R, W>(config: &Config, sources: I, mut sink: W) -> Result<Summary, Error>
+where
+    I: IntoIterator<Item = (N, R)>,
+    N: Into<String>,
+    R: Read,
+    W: Write
+{
+    unsupported("run_with", &[("--follow", config.follow.is_some())])?;
+    let mut summary = Summary::default();
+    let pipeline = Pipeline::from_config(config);
+    let result = match config.encode {
+        None => cat_sources(config, pipeline, sources, &mut sink, &mut summary),
+        Some(encoding) => {
+            let mut encoder = encoding::Encoder::new(encoding, &mut sink);
+            cat_sources(config, pipeline, sources, &mut encoder, &mut summary)
+                .and_then(|()| encoder.finish().map_err(|source| Error::Write { source }))
+        }
+    };
+    let result = result.and_then(|()| sink.flush().map_err(|source| Error::Write { source }));
+    summary.finish(result)
+}
+
+/// Same as `run_with`, but formats through `pipeline` rather than the
+/// stages a `Config` asks for. An empty pipeline copies sources as is.
+pub fn run_pipeline<I, N, R, W>(pipeline: Pipeline, sources: I, mut sink: W) -> Result<Summary, Error>
+where
+    I: IntoIterator<Item = (N, R)>,
+    N: Into<String>,
+    R: Read,
+    W: Write
+{
+    let mut summary = Summary::default();
+    let result = cat_sources(&Config::default(), pipeline, sources, &mut sink, &mut summary)
+        .and_then(|()| sink.flush().map_err(|source| Error::Write { source }));
+    summary.finish(result)
+}
+
+/// `cat`, through an encoder if `config.encode` asks for one.
+fn cat_encoded<W: Write>(
+    config: &Config,
+    out: &mut W,
+    out_is_stdout: bool,
+    summary: &mut Summary
+) -> Result<(), Error> {
+    let Some(encoding) = config.encode else {
+        return cat(config, out, out_is_stdout, summary);
+    };
+    let mut encoder = encoding::Encoder::new(encoding, out);
+    cat(config, &mut encoder, out_is_stdout, summary)?;
+    encoder.finish().map_err(|source| Error::Write { source })
+}
+
+/// Writes every file of `config` into `out`. `out_is_stdout` allows the
+/// pass-through mode to bypass `out` and let the kernel copy into stdout.
+///
+/// Problems with one input are reported and collected in `summary`; only
+/// a failure to write ends the run early.
+fn cat<W: Write>(
+    config: &Config,
+    out: &mut W,
+    out_is_stdout: bool,
+    summary: &mut Summary
+) -> Result<(), Error> {
+    let pipeline = Pipeline::from_config(config);
+    let selecting = config.lines.is_some() || config.bytes.is_some();
+    // With no stage to run, bytes can be copied without looking at them.
+    let passthrough = pipeline.is_empty() && !selecting;
+    let mut printer = Printer::new(pipeline);
+    let output = if out_is_stdout { copy::stdout_file_id() } else { None };
+    let mut headers = Headers::new(config);
+    let mut inputs = walk::inputs(config)?.peekable();
+    let mut several = false;
+
+    while let Some(filename) = inputs.next() {
+        several |= inputs.peek().is_some();
+        let prepared = filename.and_then(|filename| {
+            prepare(config, &filename, output).map(|prepared| (filename, prepared))
+        });
+        let (filename, (mut input, interactive)) = match prepared {
+            Ok(prepared) => prepared,
             Err(err) => {
-                eprintln!("{filename}: {err}");
+                summary.report(err);
                 continue;
             }
         };
 
-        let mut last_num = 0;
-        let mut blank_appear: bool = false;
-
-        for (line_num, line_result) in file.lines().enumerate() {
-            let mut line = line_result?;
-            if config.squeeze_blank && line.is_empty() {
-                if blank_appear {
-                    continue;
-                } else {
-                    blank_appear = true;
-                }
+        let follower = match (&input, config.follow) {
+            (Input::File(file), Some(mode)) if inputs.peek().is_none() && !config.hex => {
+                Follower::new(file, &filename, mode)
             }
+            _ => None
+        };
+
+        if several && headers.enabled() {
+            printer.header(&mut headers, &filename, out)?;
+        }
+        if config.hex {
+            let mut input = BufReader::new(input);
+            summary.absorb(hex::dump(&mut input, config.bytes, &filename, out, interactive))?;
+        } else if selecting {
+            let mut input = BufReader::new(input);
+            printer.select(config, &mut input, &filename, out, interactive, summary)?;
+        } else if !passthrough {
+            let mut input = BufReader::new(input);
+            printer.pipeline.start_input(&filename);
+            printer.print(&mut input, &filename, out, interactive, summary)?;
+        } else if let (true, None, Input::File(file)) = (out_is_stdout, config.encode, &mut input) {
+            out.flush().map_err(|source| Error::Write { source })?;
+            summary.absorb(copy::copy_to_stdout(file, &filename))?;
+        } else {
+            summary.absorb(copy::copy(&mut input, out, &filename, interactive))?;
+        }
+
+        if let Some(follower) = follower {
+            let result = follow(follower, &filename, &mut printer, selecting, out, summary);
+            return summary.absorb(result);
+        }
+    }
+    Ok(())
+}
 
-            if config.show_tabs {
-                line = line.replace("\t", "^I");
+/// `-f`: prints what `follower` finds appended to `path` until writing
+/// fails, or following does. `selected` tells that the input was read
+/// for `--lines` or `--bytes`, which only apply to what was there at first.
+fn follow<W: Write>(
+    mut follower: Follower,
+    path: &str,
+    printer: &mut Printer,
+    selected: bool,
+    out: &mut W,
+    summary: &mut Summary
+) -> Result<(), Error> {
+    let read = |source| Error::Read { path: path.to_string(), source };
+    let mut input = BufReader::new(follower.file().map_err(read)?);
+    if selected {
+        input.seek(SeekFrom::End(0)).map_err(read)?;
+    }
+
+    loop {
+        out.flush().map_err(|source| Error::Write { source })?;
+        match follower.wait().map_err(read)? {
+            Change::Grown => {}
+            Change::Truncated => {
+                summary.notice(path, "file truncated");
+                input.seek(SeekFrom::Start(0)).map_err(read)?;
+            }
+            Change::Replaced(file) => {
+                printer.print(&mut input, path, out, false, summary)?;
+                summary.notice(path, "file replaced; following the new file");
+                input = BufReader::new(file);
             }
+        }
+        printer.print(&mut input, path, out, false, summary)?;
+    }
+}
 
-            if config.show_ends {
-                line.push_str("$");
+/// Opens `filename` for `cat` and checks that it isn't `output`. Also
+/// returns whether the input is a terminal.
+fn prepare(
+    config: &Config,
+    filename: &str,
+    output: Option<(u64, u64)>
+) -> Result<(Input, bool), Error> {
+    let mut input = open_input(filename, config.archive_members)?;
+    let interactive = match &mut input {
+        Input::File(file) => {
+            if output.is_some_and(|output| copy::is_output(file, output)) {
+                return Err(Error::InputIsOutput { path: filename.to_string() });
             }
+            file.is_terminal()
+        }
+        Input::Reader(_) => false
+    };
 
-            if config.show_nonprinting {
-                line = line.chars().map(
-                    |c| match c {
-                        '\x01'..='\x1E' =>
-                            "^".to_string() + 
-                            &(char::from_u32(c as u32 + 0x40)
-                                .unwrap()).to_string(),
-
-                        '\x7F' => "^?".to_string(),
-
-                        '\u{0080}'..='\u{00FF}'
-                            => "M-".to_string() +
-                                &(c as u32).to_string(),
-
-                        _ => c.to_string()
-                    }
-                ).collect();
-            }            
-
-            if config.number_lines {
-                println!("{:6}\t{line}", line_num + 1);
-            } else if config.number_nonblank_lines {
-                if line.is_empty() {
-                    println!();
-                } else {
-                    last_num += 1;
-                    println!("{:6}\t{line}", last_num);
+    if let Some(encoding) = config.decode {
+        input = Input::Reader(Box::new(encoding::Decoder::new(encoding, input)));
+    }
+    // Nobody types compressed data, and waiting for its magic bytes
+    // would hold back the first lines typed.
+    if config.decompress && !interactive {
+        input = decompress::open(input, filename)?;
+    }
+    Ok((input, interactive))
+}
+
+/// `cat` for sources that are already open, formatted through `pipeline`
+/// and otherwise the way `config` says.
+fn cat_sources<I, N, R, W>(
+    config: &Config,
+    pipeline: Pipeline,
+    sources: I,
+    out: &mut W,
+    summary: &mut Summary
+) -> Result<(), Error>
+where
+    I: IntoIterator<Item = (N, R)>,
+    N: Into<String>,
+    R: Read,
+    W: Write
+{
+    let selecting = config.lines.is_some() || config.bytes.is_some();
+    let passthrough = pipeline.is_empty() && !selecting;
+    let mut printer = Printer::new(pipeline);
+    let mut headers = Headers::new(config);
+
+    let mut sources = sources.into_iter().peekable();
+    let mut several = false;
+
+    while let Some((name, source)) = sources.next() {
+        several |= sources.peek().is_some();
+        let name = name.into();
+        let mut source: Box<dyn Read + '_> = match config.decode {
+            Some(encoding) => Box::new(encoding::Decoder::new(encoding, source)),
+            None => Box::new(source)
+        };
+        if config.decompress {
+            source = match decompress::wrap(source, &name) {
+                Ok(source) => source,
+                Err(err) => {
+                    summary.report(err);
+                    continue;
                 }
-            } else {
-                println!("{line}");
-            }  
-        }        
+            };
+        }
+
+        if several && headers.enabled() {
+            printer.header(&mut headers, &name, out)?;
+        }
+        if config.hex {
+            let mut source = BufReader::new(source);
+            summary.absorb(hex::dump(&mut source, config.bytes, &name, out, false))?;
+        } else if selecting {
+            let mut source = BufReader::new(source);
+            printer.select(config, &mut source, &name, out, false, summary)?;
+        } else if passthrough {
+            summary.absorb(copy::copy(&mut source, out, &name, false))?;
+        } else {
+            printer.pipeline.start_input(&name);
+            printer.print(&mut BufReader::new(source), &name, out, false, summary)?;
+        }
     }
     Ok(())
 }
 
+/// Fails with `Error::Unsupported` for the first of `options`, named with
+/// whether they are set, that is set: `entry` can't honour it.
+fn unsupported(entry: &'static str, options: &[(&'static str, bool)]) -> Result<(), Error> {
+    match options.iter().find(|(_, set)| *set) {
+        Some(&(option, _)) => Err(Error::Unsupported { option, entry }),
+        None => Ok(())
+    }
+}
+
+/// Formatting side of a run: feeds lines read from the inputs through a
+/// `Pipeline` and writes what comes out.
+struct Printer {
+    pipeline: Pipeline,
+    line: Vec<u8>,
+    rendered: Vec<u8>
+}
+
+impl Printer {
+    fn new(pipeline: Pipeline) -> Printer {
+        Printer { pipeline, line: Vec::new(), rendered: Vec::new() }
+    }
+
+    /// Writes the header of the input `name` into `out`. The line it ends
+    /// isn't continued by the input.
+    fn header<W: Write>(&mut self, headers: &mut Headers, name: &str, out: &mut W) -> Result<(), Error> {
+        self.rendered.clear();
+        headers.render(name, &mut self.rendered);
+        self.pipeline.break_line();
+        out.write_all(&self.rendered).map_err(|source| Error::Write { source })
+    }
+
+    /// Formats the part of `input` that `config.lines` or `config.bytes`
+    /// selects into `out`. Numbering starts over for every input.
+    fn select<R: BufRead, W: Write>(
+        &mut self,
+        config: &Config,
+        input: &mut R,
+        path: &str,
+        out: &mut W,
+        interactive: bool,
+        summary: &mut Summary
+    ) -> Result<(), Error> {
+        self.pipeline = Pipeline::from_config(config);
+        self.pipeline.start_input(path);
+        let emit = |rendered: &[u8]| {
+            out.write_all(rendered)
+                .and_then(|()| if interactive { out.flush() } else { Ok(()) })
+                .map_err(|source| Error::Write { source })
+        };
+
+        let result = match (config.lines, config.bytes) {
+            (Some(range), _) => select::lines(range, &mut self.pipeline, input, path, emit),
+            (None, Some(range)) => select::bytes(range, &mut self.pipeline, input, path, emit),
+            (None, None) => Ok(())
+        };
+        summary.absorb(result)
+    }
+
+    /// Formats what is left of `input` into `out`, flushing after every
+    /// line when `interactive`. A read error is reported in `summary` and
+    /// ends this input only.
+    fn print<R: BufRead, W: Write>(
+        &mut self,
+        input: &mut R,
+        path: &str,
+        out: &mut W,
+        interactive: bool,
+        summary: &mut Summary
+    ) -> Result<(), Error> {
+        loop {
+            self.rendered.clear();
+            let next = formatter::next_line(
+                &mut self.pipeline, input, &mut self.line, &mut self.rendered
+            );
+            match next {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(source) => {
+                    summary.report(Error::Read { path: path.to_string(), source });
+                    break;
+                }
+            }
+
+            out.write_all(&self.rendered)
+                .and_then(|()| if interactive { out.flush() } else { Ok(()) })
+                .map_err(|source| Error::Write { source })?;
+        }
+        Ok(())
+    }
+}
+
 pub fn get_args() -> RunResult<Config> {
-    let matches = command!()
+    let matches = cli().get_matches();
+    check_features(&matches).unwrap_or_else(|err| err.exit());
+    Ok(Config::from_matches(&matches))
+}
+
+/// Rejects the options of cargo features that weren't compiled in.
+fn check_features(matches: &ArgMatches) -> Result<(), clap::Error> {
+    if cfg!(not(feature = "highlight")) && matches.contains_id("highlight") {
+        return Err(cli().error(
+            ErrorKind::InvalidValue,
+            "--highlight: catr was built without the `highlight` feature"
+        ));
+    }
+    Ok(())
+}
+
+/// Widest `--number-width`, which is as wide as Rust formats anything.
+const MAX_NUMBER_WIDTH: u64 = u16::MAX as u64;
+
+/// The options that set up numbering the way nl does, and imply `-n`
+/// unless `-b` is given.
+const NUMBER_OPTIONS: [&str; 6] = [
+    "number_width",
+    "number_separator",
+    "number_start",
+    "number_increment",
+    "number_format",
+    "number_sections"
+];
+
+fn cli() -> Command {
+    command!()
         .args(&[
             arg!(files: [FILE] "Input file(s)")
                 .num_args(0..)
@@ -96,45 +766,364 @@ pub fn get_args() -> RunResult<Config> {
             arg!(show_ends: -E --"show-ends" "display $ at end of each line"),
             arg!(number: -n --number "Number lines")
                 .conflicts_with("number_nonblank"),
-            arg!(squeeze_blank: -s --"squeeze-blank" "suppress repeated empty output lines"),
+            arg!(number_width: --"number-width" <N> "use N columns for line numbers (default 6)")
+                .required(false)
+                .value_parser(value_parser!(u64).range(1..=MAX_NUMBER_WIDTH)),
+            arg!(number_separator: --"number-separator" <STRING> "add STRING after line numbers (default TAB)")
+                .required(false),
+            arg!(number_start: --"number-start" <N> "number lines from N (default 1)")
+                .required(false)
+                .allow_negative_numbers(true)
+                .value_parser(value_parser!(i64)),
+            arg!(number_increment: --"number-increment" <N> "add N to the line number for each line (default 1)")
+                .required(false)
+                .allow_negative_numbers(true)
+                .value_parser(value_parser!(i64)),
+            arg!(number_format: --"number-format" <FORMAT> "format line numbers left-justified (ln), right-justified (rn, default) or zero-padded (rz)")
+                .required(false)
+                .value_parser(["ln", "rn", "rz"]),
+            arg!(number_sections: --"number-sections" [CC] "split input into header, body and footer at lines of CC three, two or one times, as nl does (default \\:), and only number bodies")
+                .require_equals(true)
+                .default_missing_value("\\:"),
+            arg!(squeeze_blank: -s --"squeeze-blank" [N] "suppress repeated empty output lines, keeping at most N (default 1)")
+                .require_equals(true)
+                .default_missing_value("1")
+                .value_parser(value_parser!(u64).range(1..)),
+            arg!(squeeze_whitespace: --"squeeze-whitespace" "treat whitespace-only lines as empty when squeezing (implies -s)"),
             arg!(vT: -t "equivalent to -vT"),
             arg!(show_tabs: -T --"show-tabs" "display TAB characters as ^I"),
             arg!(ignored: -u "(ignored)"),
-            arg!(show_nonprinting: -v --"show-nonprinting" "use ^ and M- notation, except for LFD and TAB")
+            arg!(show_nonprinting: -v --"show-nonprinting" "use ^ and M- notation, except for LFD and TAB"),
+            arg!(decompress: -z --decompress "decode gzip, bzip2, xz and zstd input, recognized by its first bytes"),
+            arg!(archive_member: --"archive-member" "read FILEs of the form ARCHIVE:MEMBER from tar or zip archives, even if such a file exists"),
+            arg!(recursive: -r --recursive "read the files under directories, in name order, honouring .gitignore and skipping .git, but not other hidden files"),
+            arg!(include: --include <GLOB> "with -r, only read files matching GLOB")
+                .required(false)
+                .action(ArgAction::Append),
+            arg!(exclude: --exclude <GLOB> "with -r, skip files and directories matching GLOB")
+                .required(false)
+                .action(ArgAction::Append),
+            arg!(headers: --headers [STYLE] "print the name of each FILE before it, as ==> FILE <== (head) or in a box")
+                .require_equals(true)
+                .default_missing_value("head")
+                .value_parser(["head", "box"]),
+            arg!(header_details: --"header-details" "add the size and modification time of files to headers (implies --headers)"),
+            arg!(lines: --lines <RANGE> "only print lines START:END of each FILE, counting from 1, or back from -1 for the last line")
+                .required(false)
+                .value_parser(LineRange::from_str),
+            arg!(bytes: --bytes <RANGE> "only print LEN bytes of each FILE from OFFSET on, as OFFSET:LEN")
+                .required(false)
+                .value_parser(ByteRange::from_str)
+                .conflicts_with("lines"),
+            arg!(follow: -f --follow [HOW] "keep printing what is appended to the last FILE, following its descriptor (default) or its name")
+                .require_equals(true)
+                .default_missing_value("descriptor")
+                .value_parser(["descriptor", "name"]),
+            arg!(highlight: --highlight [LANG] "color lines as source code in LANG, or as detected from file names and first lines (auto, the default)")
+                .require_equals(true)
+                .default_missing_value("auto")
+                .value_parser(parse_language),
+            arg!(color: --color [WHEN] "color line numbers, markers and headers: auto (the default, on a terminal), always or never")
+                .require_equals(true)
+                .default_value("auto")
+                .default_missing_value("always")
+                .value_parser(["auto", "always", "never"]),
+            arg!(hex: --hex "show FILEs as hexdump -C does, with offsets, hex bytes and text")
+                .conflicts_with_all(["lines", "follow"]),
+            arg!(decode: --decode <ENCODING> "decode FILEs from hex, base64 or base32 before anything else")
+                .required(false)
+                .value_parser(["hex", "base64", "base32"]),
+            arg!(encode: --encode <ENCODING> "encode the output as hex, base64 or base32")
+                .required(false)
+                .value_parser(["hex", "base64", "base32"]),
+            arg!(paging: --paging [WHEN] "page the output with $PAGER (less -R by default): auto (the default, when it doesn't fit on the terminal), always or never")
+                .require_equals(true)
+                .default_missing_value("auto")
+                .value_parser(["auto", "always", "never"])
         ]) 
-        .get_matches();
-    
-    let files = matches.get_many::<String>("files")
-        .unwrap()
-        .map(String::clone)
-        .collect();
-    
-    let (show_all, vt, ve) = (
-        matches.get_flag("show_all"),
-        matches.get_flag("vT"),
-        matches.get_flag("vE")
-    );
-
-    Ok(Config {
-        files,
-        number_lines: matches.get_flag("number"),
-        number_nonblank_lines: matches.get_flag("number_nonblank"),
-        show_tabs:
-            matches.get_flag("show_tabs") ||
-            show_all || vt,
-        show_ends:
-            matches.get_flag("show_ends") ||
-            show_all || ve,
-        show_nonprinting:
-            matches.get_flag("show_nonprinting") ||
-            show_all || ve || vt,
-        squeeze_blank: matches.get_flag("squeeze_blank")
-    })
-}
-
-pub fn open(filename: &str) -> RunResult<Box<dyn BufRead>> {
-    match filename {
-        "-" => Ok(Box::new(BufReader::new(io::stdin()))),
-        _ => Ok(Box::new(BufReader::new(File::open(filename)?)))
+}
+
+#[cfg(feature = "highlight")]
+use highlight::parse_language;
+
+/// Languages are checked by `check_features`.
+#[cfg(not(feature = "highlight"))]
+fn parse_language(language: &str) -> Result<String, String> {
+    Ok(language.to_string())
+}
+
+/// Opens `filename` for reading: a path, `-` for stdin, or
+/// `ARCHIVE:MEMBER` for a member of a tar or zip archive when no file has
+/// that name.
+pub fn open(filename: &str) -> Result<Box<dyn BufRead>, Error> {
+    Ok(Box::new(BufReader::new(open_input(filename, false)?)))
+}
+
+/// Same as `open`, decoding the input if it is compressed, as `-z` does.
+pub fn open_decompressed(filename: &str) -> Result<Box<dyn BufRead>, Error> {
+    let input = decompress::open(open_input(filename, false)?, filename)?;
+    Ok(Box::new(BufReader::new(input)))
+}
+
+/// An open input: a file, which can be handed to the kernel to copy, or
+/// any other reader.
+enum Input {
+    File(File),
+    Reader(Box<dyn Read>)
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::File(file) => file.read(buf),
+            Input::Reader(reader) => reader.read(buf)
+        }
+    }
+}
+
+/// Opens `filename` as a file, or as an archive member if it looks like
+/// one and either `archive_members` is set or there is no such file.
+fn open_input(filename: &str, archive_members: bool) -> Result<Input, Error> {
+    if let Some((archive, member)) = archive::split(filename) {
+        if archive_members || !Path::new(filename).exists() {
+            return archive::open(archive, member, filename).map(Input::Reader);
+        }
+    }
+    open_file(filename).map(Input::File)
+}
+
+/// Opens `filename` as a `File`, duplicating the stdin descriptor for `-`.
+fn open_file(filename: &str) -> Result<File, Error> {
+    let open = |source| Error::Open { path: filename.to_string(), source };
+
+    if filename == "-" {
+        return copy::stdin_file().map_err(open);
+    }
+
+    let file = File::open(filename).map_err(open)?;
+    if file.metadata().is_ok_and(|meta| meta.is_dir()) {
+        return Err(Error::IsDirectory { path: filename.to_string() });
+    }
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use transform::{Number, ShowEnds, ShowNonprinting, ShowTabs, Squeeze};
+
+    fn apply<T: LineTransform>(stage: &mut T, raw: &[u8]) -> Option<Line> {
+        let mut line = Line::new(raw, true);
+        stage.apply(&mut line).then_some(line)
+    }
+
+    fn render(pipeline: &mut Pipeline, lines: &[&[u8]]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for raw in lines {
+            if let Some(line) = pipeline.process(raw) {
+                line.render(&mut out);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn line_new() {
+        let line = Line::new(b"abc\n", true);
+        assert_eq!(line.content, b"abc");
+        assert!(line.has_newline);
+        assert!(!line.blank);
+
+        let line = Line::new(b"", false);
+        assert!(!line.has_newline);
+        assert!(!line.blank);
+        assert!(Line::new(b"\n", true).blank);
+    }
+
+    #[test]
+    fn squeeze() {
+        let mut squeeze = Squeeze::new(1, false);
+        let kept: Vec<bool> = [&b"a\n"[..], b"\n", b"\n", b"b\n", b"\n", b" \n"]
+            .iter()
+            .map(|raw| apply(&mut squeeze, raw).is_some())
+            .collect();
+        assert_eq!(kept, [true, true, false, true, true, true]);
+    }
+
+    #[test]
+    fn squeeze_run_and_whitespace() {
+        let mut squeeze = Squeeze::new(2, true);
+        let kept: Vec<bool> = [&b"\n"[..], b" \t\n", b"\n", b"x\n", b"\r\n"]
+            .iter()
+            .map(|raw| apply(&mut squeeze, raw).is_some())
+            .collect();
+        assert_eq!(kept, [true, true, false, true, true]);
+    }
+
+    #[test]
+    fn squeeze_keeps_line_tails() {
+        let mut squeeze = Squeeze::new(1, false);
+        assert!(apply(&mut squeeze, b"\n").is_some());
+        let mut tail = Line::new(b"\n", false);
+        assert!(squeeze.apply(&mut tail));
+    }
+
+    #[test]
+    fn show_tabs() {
+        let line = apply(&mut ShowTabs, b"\ta\tb\n").unwrap();
+        assert_eq!(line.content, b"^Ia^Ib");
+    }
+
+    #[test]
+    fn show_ends() {
+        assert_eq!(apply(&mut ShowEnds, b"a\n").unwrap().content, b"a$");
+        assert_eq!(apply(&mut ShowEnds, b"a\r\n").unwrap().content, b"a^M$");
+        assert_eq!(apply(&mut ShowEnds, b"a\r").unwrap().content, b"a\r");
+    }
+
+    #[test]
+    fn show_nonprinting() {
+        let line = apply(&mut ShowNonprinting, b"\x00\t\x1b\x7f\x80\x89\xe9\xff\n").unwrap();
+        assert_eq!(line.content, b"^@\t^[^?M-^@M-^IM-iM-^?");
+    }
+
+    #[test]
+    fn number_all() {
+        let mut number = Number::all();
+        assert_eq!(apply(&mut number, b"a\n").unwrap().prefix, b"     1\t");
+        assert_eq!(apply(&mut number, b"\n").unwrap().prefix, b"     2\t");
+        let mut tail = Line::new(b"b\n", false);
+        number.apply(&mut tail);
+        assert!(tail.prefix.is_empty());
+    }
+
+    #[test]
+    fn number_nonblank() {
+        let mut number = Number::nonblank();
+        assert!(apply(&mut number, b"\n").unwrap().prefix.is_empty());
+        assert_eq!(apply(&mut number, b"a\n").unwrap().prefix, b"     1\t");
+    }
+
+    #[test]
+    fn number_styles() {
+        let style = |format| NumberStyle {
+            width: 3,
+            separator: "|".to_string(),
+            format,
+            start: 5,
+            increment: -2
+        };
+        let mut left = Number::all().with_style(style(NumberFormat::Left));
+        assert_eq!(apply(&mut left, b"a\n").unwrap().prefix, b"5  |");
+        let mut zeros = Number::all().with_style(style(NumberFormat::Zeros));
+        assert_eq!(apply(&mut zeros, b"a\n").unwrap().prefix, b"005|");
+        assert_eq!(apply(&mut zeros, b"a\n").unwrap().prefix, b"003|");
+
+        let mut last = Number::all().with_style(NumberStyle {
+            start: i64::MAX,
+            increment: 1,
+            ..style(NumberFormat::Left)
+        });
+        assert_eq!(apply(&mut last, b"a\n").unwrap().prefix, b"9223372036854775807|");
+        assert_eq!(apply(&mut last, b"b\n").unwrap().prefix, b"9223372036854775807|");
+    }
+
+    #[test]
+    fn number_sections() {
+        let mut pipeline = Pipeline::from_config(&Config {
+            number_lines: true,
+            number_sections: Some("\\:".to_string()),
+            ..Config::default()
+        });
+        let out = render(
+            &mut pipeline,
+            &[b"a\n", b"\\:\\:\\:\n", b"head\n", b"\\:\\:\n", b"b\n", b"\\:\n", b"foot\n"]
+        );
+        assert_eq!(out, b"     1\ta\n\n       head\n\n     1\tb\n\n       foot\n");
+    }
+
+    #[test]
+    fn color_markers() {
+        let mut pipeline = Pipeline::from_config(&Config {
+            number_lines: true,
+            show_tabs: true,
+            show_ends: true,
+            show_nonprinting: true,
+            color: ColorChoice::Always,
+            ..Config::default()
+        });
+        let out = render(&mut pipeline, &[b"\ta\x01\xe9\r\n"]);
+        assert_eq!(
+            out,
+            b"\x1b[32m     1\t\x1b[0m\x1b[35m^I\x1b[0ma\x1b[35m^AM-i^M$\x1b[0m\n"
+        );
+
+        let mut tail = Line::new(b"x\ty", false);
+        tail.markers.push(0..1);
+        ShowTabs.apply(&mut tail);
+        assert_eq!(tail.markers, [0..1, 1..3]);
+    }
+
+    #[test]
+    fn pipeline_order() {
+        let config = Config {
+            number_nonblank_lines: true,
+            show_tabs: true,
+            show_ends: true,
+            show_nonprinting: true,
+            squeeze_blank: Some(1),
+            ..Config::default()
+        };
+        let mut pipeline = Pipeline::from_config(&config);
+        assert_eq!(pipeline.len(), 5);
+
+        let out = render(&mut pipeline, &[b"\ta\xff\r\n", b"\n", b"\n", b"b"]);
+        assert_eq!(out, b"     1\t^IaM-^?^M$\n$\n     2\tb");
+    }
+
+    #[test]
+    fn pipeline_lines_span_inputs() {
+        let mut pipeline = Pipeline::from_config(&Config {
+            number_lines: true,
+            ..Config::default()
+        });
+        let out = render(&mut pipeline, &[b"a", b"b\n", b"c\n"]);
+        assert_eq!(out, b"     1\tab\n     2\tc\n");
+    }
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!("2:-1".parse(), Ok(LineRange { start: 2, end: Some(-1) }));
+        assert_eq!(":3".parse(), Ok(LineRange { start: 1, end: Some(3) }));
+        assert_eq!("-5".parse(), Ok(LineRange { start: -5, end: Some(-5) }));
+        assert!("1:0".parse::<LineRange>().is_err());
+        assert_eq!("10:".parse(), Ok(ByteRange { offset: 10, len: None }));
+        assert_eq!("7".parse(), Ok(ByteRange { offset: 7, len: None }));
+        assert!("1:-2".parse::<ByteRange>().is_err());
+    }
+
+    struct Upper;
+
+    impl LineTransform for Upper {
+        fn apply(&mut self, line: &mut Line) -> bool {
+            line.content.make_ascii_uppercase();
+            true
+        }
+    }
+
+    #[test]
+    fn pipeline_custom_stage() {
+        let config = Config {
+            number_lines: true,
+            show_ends: true,
+            ..Config::default()
+        };
+        let mut pipeline = Pipeline::from_config(&config);
+        pipeline.insert(0, Upper);
+        assert_eq!(render(&mut pipeline, &[b"ab\n"]), b"     1\tAB$\n");
+
+        let mut pipeline = Pipeline::new();
+        pipeline.push(ShowEnds).push(ShowTabs);
+        assert_eq!(render(&mut pipeline, &[b"\t\n"]), b"^I$\n");
     }
 }
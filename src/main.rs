@@ -1,6 +1,31 @@
+use catr::WriteOutcome;
+
 fn main() {
-    if let Err(e) = catr::get_args().and_then(catr::run) {
-        eprintln!("{e}");
-        std::process::exit(1);
+    match catr::get_args().and_then(|config| Ok(catr::run(config)?)) {
+        Ok(summary) if summary.outcome == WriteOutcome::Closed => die_of_sigpipe(),
+        Ok(summary) if summary.is_success() => {}
+        Ok(_) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("catr: {e}");
+            std::process::exit(1);
+        }
     }
 }
+
+/// Ends the process the way it would have ended had Rust not ignored
+/// SIGPIPE, so the shell sees the usual status of a pipeline cut short.
+#[cfg(unix)]
+fn die_of_sigpipe() -> ! {
+    // SAFETY: restoring the default disposition and raising the signal
+    // on ourselves has no memory safety implications.
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+        libc::raise(libc::SIGPIPE);
+    }
+    std::process::exit(128 + libc::SIGPIPE)
+}
+
+#[cfg(not(unix))]
+fn die_of_sigpipe() -> ! {
+    std::process::exit(1)
+}
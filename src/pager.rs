@@ -0,0 +1,252 @@
+//! `--paging`: sends the output of `run` through `$PAGER`, `less -R`
+//! unless set; `less` always gets `-R`, for colors. In auto mode the
+//! pager is only started once the output turns out not to fit on the
+//! screen; until then it is held back.
+
+use crate::{Config, Error, Summary, OUT_BUF_SIZE};
+use std::env;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+const DEFAULT_PAGER: &str = "less -R";
+
+/// When `--paging` pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagingMode {
+    /// When stdout is a terminal and the output is longer than the
+    /// screen.
+    Auto,
+    /// Whatever stdout is.
+    Always
+}
+
+/// `run` through a pager. A pager quitting before the end isn't an error:
+/// the reader has seen enough.
+pub fn run(config: &Config, mode: PagingMode) -> Result<Summary, Error> {
+    let mut pager = Pager::new(mode);
+    let mut summary = Summary::verbose();
+    let mut out = BufWriter::with_capacity(OUT_BUF_SIZE, &mut pager);
+    let result = crate::cat_encoded(config, &mut out, false, &mut summary)
+        .and_then(|()| out.flush().map_err(|source| Error::Write { source }));
+    // What is still buffered after an error is of no use to anyone.
+    let _ = out.into_parts();
+
+    let result = match result {
+        Err(Error::Write { source }) if pager.closed(&source) => Ok(()),
+        result => result
+    };
+    pager.wait();
+    summary.finish(result)
+}
+
+enum State {
+    /// Holding the output back until it fills the screen or ends.
+    Holding(Vec<u8>),
+    Stdout(io::Stdout),
+    Paging(Child, ChildStdin)
+}
+
+struct Pager {
+    state: State,
+    screen: Screen,
+    /// Rows taken by what is held back, and the column it ends at.
+    rows: usize,
+    column: usize,
+    escape: bool
+}
+
+impl Pager {
+    fn new(mode: PagingMode) -> Pager {
+        let mut pager = Pager {
+            state: State::Holding(Vec::new()),
+            screen: Screen::size(),
+            rows: 0,
+            column: 0,
+            escape: false
+        };
+        if mode == PagingMode::Always {
+            pager.start();
+        }
+        pager
+    }
+
+    /// Starts the pager and hands it what was held back, or goes on
+    /// without one if it can't be started.
+    fn start(&mut self) {
+        let command = env::var("PAGER")
+            .ok()
+            .filter(|command| !command.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_PAGER.to_string());
+        let mut args = words(&command);
+        let program = if args.is_empty() { String::new() } else { args.remove(0) };
+        if is_less(&program) && !args.iter().any(|arg| raw_control_chars(arg)) {
+            args.push("-R".to_string());
+        }
+
+        let held = match &mut self.state {
+            State::Holding(held) => std::mem::take(held),
+            _ => return
+        };
+        let spawned = Command::new(&program).args(args).stdin(Stdio::piped()).spawn();
+        self.state = match spawned {
+            Ok(mut child) => {
+                let stdin = child.stdin.take().expect("stdin is piped");
+                State::Paging(child, stdin)
+            }
+            Err(err) => {
+                eprintln!("catr: cannot run pager '{command}': {err}");
+                State::Stdout(io::stdout())
+            }
+        };
+        // A write error here shows again on the next write.
+        let _ = self.write_all(&held);
+    }
+
+    /// Stops holding the output back, which fits on the screen.
+    fn release(&mut self) -> io::Result<()> {
+        if let State::Holding(held) = &mut self.state {
+            let held = std::mem::take(held);
+            self.state = State::Stdout(io::stdout());
+            self.write_all(&held)?;
+        }
+        Ok(())
+    }
+
+    /// Whether `err` comes from the pager having quit.
+    fn closed(&self, err: &io::Error) -> bool {
+        matches!(self.state, State::Paging(..)) && err.kind() == io::ErrorKind::BrokenPipe
+    }
+
+    /// Ends the input of the pager and lets the reader page through it.
+    fn wait(mut self) {
+        let _ = self.release();
+        if let State::Paging(mut child, stdin) = self.state {
+            drop(stdin);
+            let _ = child.wait();
+        }
+    }
+
+    /// Counts the rows `buf` takes on the screen, skipping escape
+    /// sequences and wrapping long lines.
+    fn measure(&mut self, buf: &[u8]) {
+        for &byte in buf {
+            if self.escape {
+                self.escape = !byte.is_ascii_alphabetic();
+                continue;
+            }
+            match byte {
+                b'\n' => {
+                    self.rows += 1;
+                    self.column = 0;
+                }
+                0x1b => self.escape = true,
+                b'\t' => self.column = (self.column / 8 + 1) * 8,
+                // UTF-8 continuation bytes
+                0x80..=0xBF => {}
+                _ => self.column += 1
+            }
+            if self.column >= self.screen.columns {
+                self.rows += 1;
+                self.column -= self.screen.columns;
+            }
+        }
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.state {
+            State::Holding(held) => held.extend_from_slice(buf),
+            State::Stdout(stdout) => return stdout.write(buf),
+            State::Paging(_, stdin) => return stdin.write(buf)
+        }
+        self.measure(buf);
+        // The last row is the pager's.
+        if self.rows + 1 >= self.screen.rows {
+            self.start();
+        }
+        Ok(buf.len())
+    }
+
+    /// Output that has to be seen now, e.g. while following a file,
+    /// stops being held back.
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.state {
+            State::Holding(_) => self.release(),
+            State::Stdout(stdout) => stdout.flush(),
+            State::Paging(_, stdin) => stdin.flush()
+        }
+    }
+}
+
+/// Splits `command` into words as a shell would, minding quotes and
+/// backslashes but nothing else.
+fn words(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = command.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (Some(open), c) if c == open => quote = None,
+            (None, '\\') => word.get_or_insert_with(String::new).extend(chars.next()),
+            (Some('"'), '\\') if matches!(chars.peek(), Some('"' | '\\')) => {
+                word.get_or_insert_with(String::new).extend(chars.next());
+            }
+            (_, c) => word.get_or_insert_with(String::new).push(c)
+        }
+    }
+    words.extend(word);
+    words
+}
+
+fn is_less(program: &str) -> bool {
+    Path::new(program).file_stem().is_some_and(|name| name == "less")
+}
+
+/// Whether `arg` has less show escapes as they are, rather than as text,
+/// which colors need.
+fn raw_control_chars(arg: &str) -> bool {
+    match arg.strip_prefix("--") {
+        Some(long) => long.eq_ignore_ascii_case("raw-control-chars"),
+        None => arg.starts_with('-') && arg.contains(['R', 'r'])
+    }
+}
+
+/// The size of the terminal, or of the usual 80x24 one.
+struct Screen {
+    rows: usize,
+    columns: usize
+}
+
+impl Screen {
+    fn size() -> Screen {
+        let (rows, columns) = terminal_size().unwrap_or((0, 0));
+        let pick = |size: usize, name, default| match size {
+            0 => env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default),
+            size => size
+        };
+        Screen { rows: pick(rows, "LINES", 24), columns: pick(columns, "COLUMNS", 80).max(1) }
+    }
+}
+
+#[cfg(unix)]
+fn terminal_size() -> Option<(usize, usize)> {
+    use std::os::fd::AsRawFd;
+
+    let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    // SAFETY: TIOCGWINSZ only writes a `winsize` into the one passed.
+    let ok = unsafe { libc::ioctl(io::stdout().as_raw_fd(), libc::TIOCGWINSZ, &mut size) } == 0;
+    ok.then_some((size.ws_row as usize, size.ws_col as usize))
+}
+
+#[cfg(not(unix))]
+fn terminal_size() -> Option<(usize, usize)> {
+    None
+}
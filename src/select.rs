@@ -0,0 +1,193 @@
+//! `--lines` and `--bytes`: the part of each input to print.
+//!
+//! Lines left out still go through the pipeline, so that numbering and
+//! squeezing come out the same as for the whole input.
+
+use crate::{Error, Pipeline};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+
+/// `--lines=START:END`: lines `start` to `end` of each input, both
+/// included and counted from 1. Negative numbers count back from the last
+/// line, which is -1; `end: None` goes to the last line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: i64,
+    pub end: Option<i64>
+}
+
+/// `--bytes=OFFSET:LEN`: `len` bytes of each input, or all of them with
+/// `None`, from `offset` on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub offset: u64,
+    pub len: Option<u64>
+}
+
+/// Why a range didn't parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseRangeError(&'static str);
+
+impl fmt::Display for ParseRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl std::error::Error for ParseRangeError {}
+
+impl FromStr for LineRange {
+    type Err = ParseRangeError;
+
+    /// `START:END`, either of which can be left out, or a single line.
+    fn from_str(s: &str) -> Result<LineRange, ParseRangeError> {
+        let invalid = ParseRangeError("expected START:END, with line numbers other than 0");
+        let number = |s: &str| match s.parse::<i64>() {
+            Ok(0) | Err(_) => Err(invalid.clone()),
+            Ok(n) => Ok(n)
+        };
+
+        let (start, end) = s.split_once(':').unwrap_or((s, s));
+        Ok(LineRange {
+            start: if start.is_empty() { 1 } else { number(start)? },
+            end: if end.is_empty() { None } else { Some(number(end)?) }
+        })
+    }
+}
+
+impl FromStr for ByteRange {
+    type Err = ParseRangeError;
+
+    /// `OFFSET:LEN`, either of which can be left out.
+    fn from_str(s: &str) -> Result<ByteRange, ParseRangeError> {
+        let invalid = ParseRangeError("expected OFFSET:LEN, with numbers of bytes");
+        let number = |s: &str| s.parse::<u64>().map_err(|_| invalid.clone());
+
+        let (offset, len) = s.split_once(':').unwrap_or((s, ""));
+        Ok(ByteRange {
+            offset: if offset.is_empty() { 0 } else { number(offset)? },
+            len: if len.is_empty() { None } else { Some(number(len)?) }
+        })
+    }
+}
+
+/// Formats the lines of `input` that `range` selects, handing them to
+/// `emit`. Reading stops after the last line selected, unless the range
+/// counts from the end.
+pub fn lines<R, F>(
+    range: LineRange,
+    pipeline: &mut Pipeline,
+    input: &mut R,
+    path: &str,
+    mut emit: F
+) -> Result<(), Error>
+where
+    R: BufRead,
+    F: FnMut(&[u8]) -> Result<(), Error>
+{
+    let back = |n: i64| n.unsigned_abs();
+    // Without negative numbers, lines can be printed as they come.
+    let from_start = range.start > 0 && range.end.is_none_or(|end| end > 0);
+    let mut raw = Vec::new();
+    // Lines that may be printed once the number of lines is known.
+    let mut held: VecDeque<(u64, Vec<u8>)> = VecDeque::new();
+    let mut number = 0;
+
+    loop {
+        raw.clear();
+        let n = input.read_until(b'\n', &mut raw)
+            .map_err(|source| Error::Read { path: path.to_string(), source })?;
+        if n == 0 {
+            break;
+        }
+        number += 1;
+        let past_end = range.end.is_some_and(|end| end > 0 && number > end as u64);
+        if past_end && from_start {
+            return Ok(());
+        }
+
+        let Some(line) = pipeline.process(&raw) else {
+            continue;
+        };
+        if past_end || (range.start > 0 && number < range.start as u64) {
+            continue;
+        }
+        let mut rendered = Vec::new();
+        line.render(&mut rendered);
+        if from_start {
+            emit(&rendered)?;
+            continue;
+        }
+
+        held.push_back((number, rendered));
+        if range.start < 0 {
+            // Only the last `-start` lines can make it.
+            while held.front().is_some_and(|&(n, _)| number - n >= back(range.start)) {
+                held.pop_front();
+            }
+        } else if let Some(end) = range.end {
+            // A line followed by `-end - 1` others makes it.
+            while held.front().is_some_and(|&(n, _)| number - n + 1 >= back(end)) {
+                let (_, rendered) = held.pop_front().unwrap();
+                emit(&rendered)?;
+            }
+        }
+    }
+
+    let total = number;
+    let resolve = |n: i64| if n > 0 { n as u64 } else { (total + 1).saturating_sub(back(n)) };
+    let (start, end) = (resolve(range.start), range.end.map_or(total, resolve));
+    for (n, rendered) in held {
+        if (start..=end).contains(&n) {
+            emit(&rendered)?;
+        }
+    }
+    Ok(())
+}
+
+/// Formats the bytes of `input` that `range` selects, handing them to
+/// `emit`. A line cut by the range goes through the pipeline as two
+/// pieces, so the piece printed at the start isn't numbered.
+pub fn bytes<R, F>(
+    range: ByteRange,
+    pipeline: &mut Pipeline,
+    input: &mut R,
+    path: &str,
+    mut emit: F
+) -> Result<(), Error>
+where
+    R: BufRead,
+    F: FnMut(&[u8]) -> Result<(), Error>
+{
+    let end = range.len.map_or(u64::MAX, |len| range.offset.saturating_add(len));
+    let mut raw = Vec::new();
+    let mut rendered = Vec::new();
+    let mut pos = 0;
+
+    while pos < end {
+        raw.clear();
+        let n = input.read_until(b'\n', &mut raw)
+            .map_err(|source| Error::Read { path: path.to_string(), source })?;
+        if n == 0 {
+            break;
+        }
+
+        let (from, to) = (pos, pos + n as u64);
+        pos = to;
+        let keep_from = (range.offset.clamp(from, to) - from) as usize;
+        let keep_to = (end.clamp(from, to) - from) as usize;
+        if keep_from > 0 {
+            pipeline.process(&raw[..keep_from]);
+        }
+        if keep_to > keep_from {
+            if let Some(line) = pipeline.process(&raw[keep_from..keep_to]) {
+                rendered.clear();
+                line.render(&mut rendered);
+                emit(&rendered)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,478 @@
+//! The per-line stages behind cat's formatting options.
+//!
+//! `Pipeline::from_config` lines the stages up in the order GNU cat
+//! applies them: squeeze, tabs, ends, nonprinting, numbering, with nl's
+//! sections found right after squeezing, then highlighting and coloring.
+//! Library users can add their own stages anywhere with
+//! `Pipeline::insert`.
+
+use crate::{ColorChoice, Config};
+use std::ops::Range;
+
+/// One line on its way through a `Pipeline`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Line {
+    /// What gets printed for the line, without its newline.
+    pub content: Vec<u8>,
+    /// Printed before `content`, e.g. the line number.
+    pub prefix: Vec<u8>,
+    /// Whether the input had a newline after this line.
+    pub has_newline: bool,
+    /// False for the tail of a line that an earlier input didn't finish.
+    pub line_start: bool,
+    /// Whether the line was empty as read, before any stage ran.
+    pub blank: bool,
+    /// The part of nl's logical page the line is in, as found by
+    /// `Sections`.
+    pub section: Section,
+    /// Whether the line was a section delimiter, now emptied.
+    pub delimiter: bool,
+    /// The parts of `content` that stand for other bytes, like `^I`, in
+    /// order, for `--color` to set apart.
+    pub markers: Vec<Range<usize>>
+}
+
+/// A part of a logical page, as nl sees its input. Lines are in the body
+/// unless delimiter lines say otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Section {
+    Header,
+    #[default]
+    Body,
+    Footer
+}
+
+impl Line {
+    /// Builds a line from the raw bytes read, newline included if any.
+    pub fn new(raw: &[u8], line_start: bool) -> Line {
+        let has_newline = raw.last() == Some(&b'\n');
+        let content = raw[..raw.len() - has_newline as usize].to_vec();
+        Line {
+            blank: line_start && content.is_empty(),
+            content,
+            prefix: Vec::new(),
+            has_newline,
+            line_start,
+            section: Section::Body,
+            delimiter: false,
+            markers: Vec::new()
+        }
+    }
+
+    /// Appends the line as it should be printed to `out`.
+    pub fn render(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.prefix);
+        out.extend_from_slice(&self.content);
+        if self.has_newline {
+            out.push(b'\n');
+        }
+    }
+}
+
+/// A stage of the pipeline. Stages keep whatever state they need across
+/// lines and inputs, e.g. the next line number.
+pub trait LineTransform: Send {
+    /// Rewrites `line` in place; returning false drops it from the output.
+    fn apply(&mut self, line: &mut Line) -> bool;
+
+    /// Called before the lines of each input, with the name its errors
+    /// are reported under.
+    fn start_input(&mut self, _name: &str) {}
+}
+
+/// An ordered list of `LineTransform`s applied to every line.
+pub struct Pipeline {
+    stages: Vec<Box<dyn LineTransform>>,
+    at_line_start: bool
+}
+
+impl Default for Pipeline {
+    fn default() -> Pipeline {
+        Pipeline::new()
+    }
+}
+
+impl Pipeline {
+    /// A pipeline without stages, which prints lines unchanged.
+    pub fn new() -> Pipeline {
+        Pipeline { stages: Vec::new(), at_line_start: true }
+    }
+
+    /// The stages `config` asks for, in GNU cat's order.
+    pub fn from_config(config: &Config) -> Pipeline {
+        let mut pipeline = Pipeline::new();
+        if let Some(max_run) = config.squeeze_blank {
+            pipeline.push(Squeeze::new(max_run, config.squeeze_whitespace));
+        }
+        if let Some(delimiter) = &config.number_sections {
+            pipeline.push(Sections::new(delimiter.as_bytes()));
+        }
+        if config.show_tabs {
+            pipeline.push(ShowTabs);
+        }
+        if config.show_ends {
+            pipeline.push(ShowEnds);
+        }
+        if config.show_nonprinting {
+            pipeline.push(ShowNonprinting);
+        }
+        let number = if config.number_nonblank_lines {
+            Some(Number::nonblank())
+        } else if config.number_lines {
+            Some(Number::all())
+        } else {
+            None
+        };
+        if let Some(number) = number {
+            let number = number.with_style(config.number_style.clone());
+            pipeline.push(match config.number_sections {
+                Some(_) => number.by_section(),
+                None => number
+            });
+        }
+        #[cfg(feature = "highlight")]
+        if let Some(language) = &config.highlight {
+            pipeline.push(crate::Highlight::new(language));
+        }
+        // Plain copies have nothing to color.
+        if config.color == ColorChoice::Always && !pipeline.is_empty() {
+            pipeline.push(crate::Colorize);
+        }
+        pipeline
+    }
+
+    /// Adds `stage` after the existing ones.
+    pub fn push<T: LineTransform + 'static>(&mut self, stage: T) -> &mut Pipeline {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Adds `stage` at `index`, so that it runs before the stage that was
+    /// there. Panics if `index > len`.
+    pub fn insert<T: LineTransform + 'static>(&mut self, index: usize, stage: T) -> &mut Pipeline {
+        self.stages.insert(index, Box::new(stage));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    /// Makes the next line start a new line even if the last input ended
+    /// without a newline, for when something printed in between ended it.
+    pub fn break_line(&mut self) {
+        self.at_line_start = true;
+    }
+
+    /// Tells every stage that the lines to come are those of `name`.
+    pub fn start_input(&mut self, name: &str) {
+        for stage in &mut self.stages {
+            stage.start_input(name);
+        }
+    }
+
+    /// Runs one line, as read and newline included, through every stage.
+    /// Returns `None` when a stage dropped it.
+    ///
+    /// Calls for consecutive inputs continue the same stream: a line read
+    /// without a newline is continued by the next call.
+    pub fn process(&mut self, raw: &[u8]) -> Option<Line> {
+        let mut line = Line::new(raw, self.at_line_start);
+        self.at_line_start = line.has_newline;
+
+        for stage in &mut self.stages {
+            if !stage.apply(&mut line) {
+                return None;
+            }
+        }
+        Some(line)
+    }
+}
+
+/// `-s`: lets through at most `max_run` blank lines in a row, starting
+/// over at every line that isn't blank.
+pub struct Squeeze {
+    max_run: usize,
+    whitespace: bool,
+    run: usize
+}
+
+impl Squeeze {
+    /// With `whitespace`, lines of nothing but whitespace count as blank.
+    pub fn new(max_run: usize, whitespace: bool) -> Squeeze {
+        Squeeze { max_run, whitespace, run: 0 }
+    }
+}
+
+impl LineTransform for Squeeze {
+    fn apply(&mut self, line: &mut Line) -> bool {
+        let blank = line.line_start && (
+            line.content.is_empty() ||
+            self.whitespace && line.content.iter().all(u8::is_ascii_whitespace)
+        );
+
+        if blank {
+            self.run = self.run.saturating_add(1);
+            self.run <= self.max_run
+        } else {
+            self.run = 0;
+            true
+        }
+    }
+}
+
+/// `-T`: displays TAB characters as `^I`.
+pub struct ShowTabs;
+
+impl LineTransform for ShowTabs {
+    fn apply(&mut self, line: &mut Line) -> bool {
+        if line.content.contains(&b'\t') {
+            rewrite(line, |byte, shown| match byte {
+                b'\t' => {
+                    shown.extend_from_slice(b"^I");
+                    true
+                }
+                _ => {
+                    shown.push(byte);
+                    false
+                }
+            });
+        }
+        true
+    }
+}
+
+/// Replaces every byte of `line.content` with what `show` appends for it,
+/// which returns whether that is a marker. Markers already there move
+/// along with the bytes they cover.
+fn rewrite(line: &mut Line, mut show: impl FnMut(u8, &mut Vec<u8>) -> bool) {
+    let mut shown = Vec::with_capacity(line.content.len() + 8);
+    let mut markers: Vec<Range<usize>> = Vec::new();
+    let moving = !line.markers.is_empty();
+    let mut starts = Vec::new();
+
+    for &byte in &line.content {
+        let start = shown.len();
+        if moving {
+            starts.push(start);
+        }
+        if show(byte, &mut shown) {
+            match markers.last_mut() {
+                Some(last) if last.end == start => last.end = shown.len(),
+                _ => markers.push(start..shown.len())
+            }
+        }
+    }
+
+    if moving {
+        starts.push(shown.len());
+        let position = |index: usize| starts.get(index).copied().unwrap_or(shown.len());
+        markers.extend(line.markers.iter().map(|marker| position(marker.start)..position(marker.end)));
+        markers.sort_by_key(|marker| marker.start);
+    }
+    line.markers = markers;
+    line.content = shown;
+}
+
+/// `-E`: displays `$` where the newline is, and a carriage return right
+/// before it as `^M`.
+pub struct ShowEnds;
+
+impl LineTransform for ShowEnds {
+    fn apply(&mut self, line: &mut Line) -> bool {
+        if line.has_newline {
+            if line.content.last() == Some(&b'\r') {
+                line.content.pop();
+                let start = line.content.len();
+                line.content.extend_from_slice(b"^M");
+                line.markers.push(start..start + 2);
+            }
+            let start = line.content.len();
+            line.content.push(b'$');
+            line.markers.push(start..start + 1);
+        }
+        true
+    }
+}
+
+/// `-v`: rewrites control and high bytes with GNU cat's `^` and `M-`
+/// notation. Plain tabs are kept as they are; showing them is up to
+/// `ShowTabs`.
+pub struct ShowNonprinting;
+
+impl LineTransform for ShowNonprinting {
+    fn apply(&mut self, line: &mut Line) -> bool {
+        rewrite(line, |byte, shown| {
+            let meta = byte >= 0x80;
+            let low = byte & 0x7F;
+            if meta {
+                shown.extend_from_slice(b"M-");
+            }
+
+            match low {
+                b'\t' if !meta => {
+                    shown.push(b'\t');
+                    false
+                }
+
+                0x00..=0x1F => {
+                    shown.extend_from_slice(&[b'^', low + 0x40]);
+                    true
+                }
+
+                0x7F => {
+                    shown.extend_from_slice(b"^?");
+                    true
+                }
+
+                _ => {
+                    shown.push(low);
+                    meta
+                }
+            }
+        });
+        true
+    }
+}
+
+/// `--number-sections`: nl's logical pages. Lines made of the delimiter
+/// three times, twice or once start a header, a body or a footer; they are
+/// printed empty.
+pub struct Sections {
+    delimiter: Vec<u8>,
+    section: Section
+}
+
+impl Sections {
+    /// As with nl's `-d`, a delimiter of a single byte gets a `:` added.
+    pub fn new(delimiter: &[u8]) -> Sections {
+        let mut delimiter = delimiter.to_vec();
+        if delimiter.len() == 1 {
+            delimiter.push(b':');
+        }
+        Sections { delimiter, section: Section::Body }
+    }
+}
+
+impl LineTransform for Sections {
+    fn apply(&mut self, line: &mut Line) -> bool {
+        let section = match line.content.strip_prefix(&self.delimiter[..]) {
+            Some(rest) if line.line_start && !self.delimiter.is_empty() => match rest.strip_prefix(&self.delimiter[..]) {
+                Some(rest) if rest == self.delimiter => Some(Section::Header),
+                Some([]) => Some(Section::Body),
+                None if rest.is_empty() => Some(Section::Footer),
+                _ => None
+            },
+            _ => None
+        };
+        if let Some(section) = section {
+            self.section = section;
+            line.content.clear();
+            line.delimiter = true;
+        }
+        line.section = self.section;
+        true
+    }
+}
+
+/// How line numbers look; the default is cat's, `{:6}` and a tab,
+/// counting from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberStyle {
+    /// Columns the number takes at least, up to 65535.
+    pub width: usize,
+    /// Printed between the number and the line.
+    pub separator: String,
+    pub format: NumberFormat,
+    pub start: i64,
+    pub increment: i64
+}
+
+impl Default for NumberStyle {
+    fn default() -> NumberStyle {
+        NumberStyle {
+            width: 6,
+            separator: "\t".to_string(),
+            format: NumberFormat::Right,
+            start: 1,
+            increment: 1
+        }
+    }
+}
+
+/// nl's `-n` formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// `ln`: left-justified.
+    Left,
+    /// `rn`: right-justified.
+    Right,
+    /// `rz`: right-justified with leading zeros.
+    Zeros
+}
+
+/// `-n` and `-b`: prefixes lines with their number, as `NumberStyle`
+/// says.
+pub struct Number {
+    nonblank: bool,
+    by_section: bool,
+    style: NumberStyle,
+    next: i64
+}
+
+impl Number {
+    /// Numbers every line.
+    pub fn all() -> Number {
+        Number { nonblank: false, by_section: false, style: NumberStyle::default(), next: 1 }
+    }
+
+    /// Numbers lines that weren't blank as read.
+    pub fn nonblank() -> Number {
+        Number { nonblank: true, ..Number::all() }
+    }
+
+    pub fn with_style(self, style: NumberStyle) -> Number {
+        Number { next: style.start, style, ..self }
+    }
+
+    /// Only numbers lines in the body of nl's logical pages, starting over
+    /// at every section delimiter. Header and footer lines are indented
+    /// to line up, as nl does.
+    pub fn by_section(self) -> Number {
+        Number { by_section: true, ..self }
+    }
+}
+
+impl LineTransform for Number {
+    fn apply(&mut self, line: &mut Line) -> bool {
+        if line.delimiter {
+            self.next = self.style.start;
+            return true;
+        }
+        if !line.line_start || self.nonblank && line.blank {
+            return true;
+        }
+
+        let NumberStyle { separator, .. } = &self.style;
+        // `format!` panics on anything wider.
+        let width = self.style.width.min(u16::MAX.into());
+        let prefix = if self.by_section && line.section != Section::Body {
+            " ".repeat(width + separator.len())
+        } else {
+            let number = self.next;
+            // Past the end of `i64`, the last number is kept.
+            self.next = self.next.saturating_add(self.style.increment);
+            match self.style.format {
+                NumberFormat::Left => format!("{number:<width$}{separator}"),
+                NumberFormat::Right => format!("{number:>width$}{separator}"),
+                NumberFormat::Zeros => format!("{number:0width$}{separator}")
+            }
+        };
+        line.prefix.extend_from_slice(prefix.as_bytes());
+        true
+    }
+}
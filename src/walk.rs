@@ -0,0 +1,121 @@
+//! `-r`: the files under the directories given, in name order, skipping
+//! what `--include`, `--exclude`, `.gitignore` files and the like rule
+//! out. Hidden files are read like any other; only `.git` directories,
+//! which git itself never looks into, are left out.
+
+use crate::{Config, Error};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::io;
+use std::iter;
+use std::path::Path;
+use std::sync::Arc;
+
+/// `--include` and `--exclude`. Patterns are matched against the path
+/// below the directory being walked and against the file name alone, so
+/// `*.rs` and `src/*.rs` both do what they look like.
+struct Filter {
+    include: Option<GlobSet>,
+    exclude: GlobSet
+}
+
+impl Filter {
+    fn new(config: &Config) -> Result<Filter, Error> {
+        let include = if config.include.is_empty() {
+            None
+        } else {
+            Some(glob_set(&config.include)?)
+        };
+        Ok(Filter { include, exclude: glob_set(&config.exclude)? })
+    }
+
+    /// Whether to keep `relative`; only files need to match `--include`.
+    fn allows(&self, relative: &Path, is_file: bool) -> bool {
+        let matches = |set: &GlobSet| {
+            set.is_match(relative) || relative.file_name().is_some_and(|name| set.is_match(name))
+        };
+        if matches(&self.exclude) {
+            return false;
+        }
+        !is_file || self.include.as_ref().is_none_or(matches)
+    }
+}
+
+fn glob_set(patterns: &[String]) -> Result<GlobSet, Error> {
+    let mut set = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern).map_err(|err| Error::Pattern {
+            pattern: pattern.clone(),
+            message: err.kind().to_string()
+        })?;
+        set.add(glob);
+    }
+    set.build().map_err(|err| Error::Pattern {
+        pattern: err.glob().unwrap_or_default().to_string(),
+        message: err.kind().to_string()
+    })
+}
+
+/// The names of the inputs to read for `config`: its files, with every
+/// directory replaced by the files under it when `config.recursive`.
+/// Errors met while walking concern one path and don't stop the walk.
+pub fn inputs(config: &Config) -> Result<impl Iterator<Item = Result<String, Error>> + '_, Error> {
+    let filter = Arc::new(Filter::new(config)?);
+    Ok(config.files.iter().flat_map(move |filename| {
+        let inputs: Box<dyn Iterator<Item = Result<String, Error>>> =
+            if config.recursive && Path::new(filename).is_dir() {
+                Box::new(walk(filename, Arc::clone(&filter)))
+            } else {
+                Box::new(iter::once(Ok(filename.clone())))
+            };
+        inputs
+    }))
+}
+
+fn walk(dir: &str, filter: Arc<Filter>) -> impl Iterator<Item = Result<String, Error>> {
+    let root = dir.to_string();
+    let walker = ignore::WalkBuilder::new(dir)
+        // `.gitignore` files count even outside of a git checkout.
+        .require_git(false)
+        .hidden(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(move |entry| {
+            let relative = entry.path().strip_prefix(&root).unwrap_or(entry.path());
+            let is_file = !entry.file_type().is_some_and(|kind| kind.is_dir());
+            entry.depth() == 0 ||
+                (is_file || entry.file_name() != ".git") && filter.allows(relative, is_file)
+        })
+        .build();
+
+    let dir = dir.to_string();
+    walker.filter_map(move |entry| match entry {
+        Ok(entry) => {
+            // Links are read when they lead to a file, never walked into.
+            let path = entry.path();
+            if !entry.file_type().is_some_and(|kind| kind.is_file()) && !path.is_file() {
+                return None;
+            }
+            Some(path.to_str().map(str::to_string).ok_or_else(|| Error::Open {
+                path: path.to_string_lossy().into_owned(),
+                source: io::Error::new(io::ErrorKind::InvalidData, "file name is not valid UTF-8")
+            }))
+        }
+        Err(err) => Some(Err(walk_error(&dir, err)))
+    })
+}
+
+/// Turns a walking error into an `Open` error on the path it is about.
+fn walk_error(dir: &str, mut err: ignore::Error) -> Error {
+    let mut path = dir.to_string();
+    loop {
+        err = match err {
+            ignore::Error::WithPath { path: inner, err } => {
+                path = inner.display().to_string();
+                *err
+            }
+            ignore::Error::WithDepth { err, .. } |
+            ignore::Error::WithLineNumber { err, .. } => *err,
+            ignore::Error::Io(source) => return Error::Open { path, source },
+            err => return Error::Open { path, source: io::Error::other(err) }
+        }
+    }
+}
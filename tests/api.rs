@@ -0,0 +1,239 @@
+use catr::{ColorChoice, Config, Encoding, Error, FollowMode, Formatter, WriteOutcome};
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+const FOX: &str = "tests/inputs/fox.txt";
+const SPIDERS: &str = "tests/inputs/spiders.txt";
+const BUSTLE: &str = "tests/inputs/the-bustle.txt";
+
+// --------------------------------------------------
+#[test]
+fn run_with_sources() -> TestResult {
+    let config = Config {
+        number_lines: true,
+        ..Config::default()
+    };
+    let sources = [FOX, SPIDERS, BUSTLE]
+        .into_iter()
+        .map(|path| Ok((path, fs::File::open(path)?)))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut out = Vec::new();
+    let summary = catr::run_with(&config, sources, &mut out)?;
+    assert!(summary.is_success());
+    assert_eq!(out, fs::read("tests/expected/all.n.out")?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn run_with_in_memory_sources() -> TestResult {
+    let config = Config {
+        show_ends: true,
+        squeeze_blank: Some(1),
+        ..Config::default()
+    };
+    let sources = [("a", &b"one\n\n"[..]), ("b", &b"\n\ntwo"[..])];
+
+    let mut out = Vec::new();
+    catr::run_with(&config, sources, &mut out)?;
+    assert_eq!(out, b"one$\n$\ntwo");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn run_with_selects_and_decodes() -> TestResult {
+    let config = Config {
+        number_lines: true,
+        lines: Some("2:3".parse()?),
+        ..Config::default()
+    };
+    let mut out = Vec::new();
+    catr::run_with(&config, [("-", &b"1\n2\n3\n4\n"[..])], &mut out)?;
+    assert_eq!(out, b"     2\t2\n     3\t3\n");
+
+    let config = Config {
+        decode: Some(Encoding::Base64),
+        ..Config::default()
+    };
+    let mut out = Vec::new();
+    let sources = [("fox", fs::File::open("tests/inputs/fox.txt.b64")?)];
+    catr::run_with(&config, sources, &mut out)?;
+    assert_eq!(out, fs::read(FOX)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn run_with_rejects_follow() {
+    let config = Config {
+        follow: Some(FollowMode::Descriptor),
+        ..Config::default()
+    };
+    let result = catr::run_with(&config, [("-", &b"text\n"[..])], Vec::new());
+    assert!(matches!(
+        result,
+        Err(Error::Unsupported { option: "--follow", entry: "run_with" })
+    ));
+}
+
+// --------------------------------------------------
+#[test]
+fn run_to_files() -> TestResult {
+    let config = Config::try_from_args(["catr", "-b", FOX, SPIDERS, BUSTLE])?;
+
+    let mut out = Vec::new();
+    catr::run_to(&config, &mut out)?;
+    assert_eq!(out, fs::read("tests/expected/all.b.out")?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn auto_color_is_off_outside_run() -> TestResult {
+    let config = Config {
+        files: vec![FOX.to_string()],
+        number_lines: true,
+        color: ColorChoice::Auto,
+        ..Config::default()
+    };
+    assert_eq!(Config::try_from_args(["catr", "-n", FOX])?.color, ColorChoice::Auto);
+
+    let mut out = Vec::new();
+    catr::run_to(&config, &mut out)?;
+    assert_eq!(out, fs::read("tests/expected/fox.txt.n.out")?);
+
+    let mut out = Vec::new();
+    Formatter::new(&config)?.wrap(catr::open(FOX)?).read_to_end(&mut out)?;
+    assert_eq!(out, fs::read("tests/expected/fox.txt.n.out")?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn run_to_reports_failures() -> TestResult {
+    let config = Config {
+        files: vec!["tests/inputs".to_string(), FOX.to_string()],
+        ..Config::default()
+    };
+
+    let mut out = Vec::new();
+    let summary = catr::run_to(&config, &mut out)?;
+    assert!(!summary.is_success());
+    assert!(matches!(
+        summary.failures[..],
+        [Error::IsDirectory { ref path }] if path == "tests/inputs"
+    ));
+    assert_eq!(out, fs::read("tests/expected/fox.txt.out")?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn try_from_args_rejects_bad_args() {
+    assert!(Config::try_from_args(["catr", "--no-such-flag"]).is_err());
+    assert!(Config::try_from_args(["catr", "-n", "-b"]).is_err());
+}
+
+// --------------------------------------------------
+struct ClosedPipe;
+
+impl Write for ClosedPipe {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// --------------------------------------------------
+#[test]
+fn closed_sink() -> TestResult {
+    for config in [Config::default(), Config { number_lines: true, ..Config::default() }] {
+        let summary = catr::run_with(&config, [("-", &b"text\n"[..])], ClosedPipe)?;
+        assert_eq!(summary.outcome, WriteOutcome::Closed);
+        assert!(!summary.is_success());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn formatter_wrap() -> TestResult {
+    let config = Config::try_from_args(["catr", "-A"])?;
+    let mut out = Vec::new();
+    Formatter::new(&config)?
+        .wrap(catr::open("tests/inputs/latin1.txt")?)
+        .read_to_end(&mut out)?;
+    assert_eq!(out, fs::read("tests/expected/latin1.txt.A.out")?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn formatter_chained_inputs() -> TestResult {
+    let config = Config::try_from_args(["catr", "-b"])?;
+    let input = catr::open(FOX)?.chain(catr::open(SPIDERS)?).chain(catr::open(BUSTLE)?);
+    let mut out = Vec::new();
+    Formatter::new(&config)?.wrap(input).read_to_end(&mut out)?;
+    assert_eq!(out, fs::read("tests/expected/all.b.out")?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn formatter_lines() -> TestResult {
+    let config = Config {
+        number_lines: true,
+        squeeze_blank: Some(1),
+        ..Config::default()
+    };
+    let formatted = Formatter::new(&config)?.wrap(&b"a\n\n\n\nb\n"[..]);
+    let lines = formatted.lines().collect::<io::Result<Vec<_>>>()?;
+    assert_eq!(lines, ["     1\ta", "     2\t", "     3\tb"]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn formatter_rejects_selection() -> TestResult {
+    let config = Config::try_from_args(["catr", "-n", "--lines=2:3"])?;
+    assert!(matches!(
+        Formatter::new(&config),
+        Err(Error::Unsupported { option: "--lines", entry: "Formatter" })
+    ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn formatter_into_inner() -> TestResult {
+    let config = Config {
+        number_lines: true,
+        ..Config::default()
+    };
+    let mut out = String::new();
+    let mut formatted = Formatter::new(&config)?.wrap(&b"a\n"[..]);
+    formatted.read_to_string(&mut out)?;
+    let (formatter, _) = formatted.into_inner();
+    formatter.wrap(&b"b\n"[..]).read_to_string(&mut out)?;
+    assert_eq!(out, "     1\ta\n     2\tb\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn open_archive_member() -> TestResult {
+    let mut contents = Vec::new();
+    catr::open("tests/inputs/release.tar:docs/fox.txt")?.read_to_end(&mut contents)?;
+    assert_eq!(contents, fs::read(FOX)?);
+
+    let err = catr::open("tests/inputs/bundle.zip:nope.txt").err().unwrap();
+    assert_eq!(err.path(), Some("tests/inputs/bundle.zip:nope.txt"));
+    Ok(())
+}
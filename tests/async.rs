@@ -0,0 +1,92 @@
+#![cfg(feature = "async")]
+
+use catr::{AsyncFormatter, Config, Error};
+use std::fs;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+const FOX: &str = "tests/inputs/fox.txt";
+const SPIDERS: &str = "tests/inputs/spiders.txt";
+const BUSTLE: &str = "tests/inputs/the-bustle.txt";
+
+// --------------------------------------------------
+#[tokio::test]
+async fn run_async_to_files() -> TestResult {
+    for (flag, expected_file) in [
+        ("-u", "tests/expected/all.out"),
+        ("-n", "tests/expected/all.n.out"),
+        ("-b", "tests/expected/all.b.out"),
+    ] {
+        let config = Config::try_from_args(["catr", flag, FOX, SPIDERS, BUSTLE])?;
+        let mut out = Vec::new();
+        let summary = catr::run_async_to(&config, &mut out).await?;
+        assert!(summary.is_success());
+        assert_eq!(out, fs::read(expected_file)?);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[tokio::test]
+async fn run_async_to_reports_failures() -> TestResult {
+    let config = Config::try_from_args(["catr", "-n", "tests/inputs", FOX])?;
+    let mut out = Vec::new();
+    let summary = catr::run_async_to(&config, &mut out).await?;
+    assert_eq!(summary.failures.len(), 1);
+    assert_eq!(out, fs::read("tests/expected/fox.txt.n.out")?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[tokio::test]
+async fn run_async_to_rejects_decompress() -> TestResult {
+    let config = Config::try_from_args(["catr", "-z", FOX])?;
+    let result = catr::run_async_to(&config, Vec::new()).await;
+    assert!(matches!(
+        result,
+        Err(Error::Unsupported { option: "--decompress", entry: "run_async_to" })
+    ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[tokio::test]
+async fn async_formatter_wrap() -> TestResult {
+    let config = Config::try_from_args(["catr", "-A"])?;
+    let mut out = Vec::new();
+    AsyncFormatter::new(&config)?
+        .wrap(catr::open_async("tests/inputs/latin1.txt")?)
+        .read_to_end(&mut out)
+        .await?;
+    assert_eq!(out, fs::read("tests/expected/latin1.txt.A.out")?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[tokio::test]
+async fn async_formatter_rejects_decode() -> TestResult {
+    let config = Config::try_from_args(["catr", "--decode=hex"])?;
+    assert!(matches!(
+        AsyncFormatter::new(&config),
+        Err(Error::Unsupported { option: "--decode", entry: "AsyncFormatter" })
+    ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[tokio::test]
+async fn async_formatter_lines() -> TestResult {
+    let config = Config {
+        number_nonblank_lines: true,
+        squeeze_blank: Some(1),
+        ..Config::default()
+    };
+    let mut lines = AsyncFormatter::new(&config)?.wrap(&b"a\n\n\n\nb"[..]).lines();
+    let mut seen = Vec::new();
+    while let Some(line) = lines.next_line().await? {
+        seen.push(line);
+    }
+    assert_eq!(seen, ["     1\ta", "", "     2\tb"]);
+    Ok(())
+}
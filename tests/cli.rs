@@ -1,16 +1,32 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
+use std::env;
 use std::error::Error;
-use std::fs;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
 
 type TestResult = Result<(), Box<dyn Error>>;
 
+/// Output of a running catr, as it comes.
+type Chunks = mpsc::Receiver<Vec<u8>>;
+
 const PRG: &str = "catr";
 const EMPTY: &str = "tests/inputs/empty.txt";
 const FOX: &str = "tests/inputs/fox.txt";
 const SPIDERS: &str = "tests/inputs/spiders.txt";
 const BUSTLE: &str = "tests/inputs/the-bustle.txt";
+const LATIN1: &str = "tests/inputs/latin1.txt";
+const BLANK_TAIL: &str = "tests/inputs/blank-tail.txt";
+const BLANK_HEAD: &str = "tests/inputs/blank-head.txt";
+const WHITESPACE: &str = "tests/inputs/whitespace.txt";
+const FOX_GZ: &str = "tests/inputs/fox.txt.gz";
+const BUSTLE_XZ: &str = "tests/inputs/the-bustle.txt.xz";
 
 // --------------------------------------------------
 #[test]
@@ -47,14 +63,40 @@ fn skips_bad_file() -> TestResult {
     Command::cargo_bin(PRG)?
         .arg(&bad)
         .assert()
-        .success()
+        .code(1)
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn continues_after_bad_file() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = fs::read("tests/expected/fox.txt.n.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["-n", &bad, FOX])
+        .assert()
+        .code(1)
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn skips_directory() -> TestResult {
+    let expected = fs::read("tests/expected/fox.txt.out")?;
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs", FOX])
+        .assert()
+        .code(1)
+        .stdout(expected)
+        .stderr("catr: tests/inputs: Is a directory\n");
+    Ok(())
+}
+
 // --------------------------------------------------
 fn run(args: &[&str], expected_file: &str) -> TestResult {
-    let expected = fs::read_to_string(expected_file)?;
+    let expected = fs::read(expected_file)?;
     Command::cargo_bin(PRG)?
         .args(args)
         .assert()
@@ -69,8 +111,8 @@ fn run_stdin(
     args: &[&str],
     expected_file: &str,
 ) -> TestResult {
-    let input = fs::read_to_string(input_file)?;
-    let expected = fs::read_to_string(expected_file)?;
+    let input = fs::read(input_file)?;
+    let expected = fs::read(expected_file)?;
     Command::cargo_bin(PRG)?
         .args(args)
         .write_stdin(input)
@@ -198,3 +240,951 @@ fn all_n() -> TestResult {
 fn all_b() -> TestResult {
     run(&[FOX, SPIDERS, BUSTLE, "-b"], "tests/expected/all.b.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn latin1() -> TestResult {
+    run(&[LATIN1], "tests/expected/latin1.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_stdin() -> TestResult {
+    run_stdin(LATIN1, &["-"], "tests/expected/latin1.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_n() -> TestResult {
+    run(&["-n", LATIN1], "tests/expected/latin1.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_b() -> TestResult {
+    run(&["-b", LATIN1], "tests/expected/latin1.txt.b.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_show_tabs() -> TestResult {
+    run(&["-T", LATIN1], "tests/expected/latin1.txt.T.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_show_ends() -> TestResult {
+    run(&["-E", LATIN1], "tests/expected/latin1.txt.E.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_b_show_ends() -> TestResult {
+    run(&[FOX, SPIDERS, BUSTLE, "-bE"], "tests/expected/all.bE.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_n_with_stdin() -> TestResult {
+    run_stdin(SPIDERS, &["-n", FOX, "-", FOX], "tests/expected/all.n.stdin.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_s() -> TestResult {
+    run(&["-s", BLANK_TAIL, BLANK_HEAD], "tests/expected/blanks.s.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn blanks_ns() -> TestResult {
+    run(&["-ns", BLANK_TAIL, BLANK_HEAD], "tests/expected/blanks.ns.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn whitespace_s() -> TestResult {
+    run(&["-s", WHITESPACE], "tests/expected/whitespace.txt.s.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn whitespace_s2() -> TestResult {
+    run(
+        &["--squeeze-blank=2", WHITESPACE],
+        "tests/expected/whitespace.txt.s2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn whitespace_squeeze_whitespace_n() -> TestResult {
+    run(
+        &["--squeeze-whitespace", "-n", WHITESPACE],
+        "tests/expected/whitespace.txt.ws.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bustle_nl_style() -> TestResult {
+    run(
+        &[
+            "--number-format=rz",
+            "--number-width=3",
+            "--number-separator=|",
+            "--number-start=5",
+            "--number-increment=2",
+            BUSTLE,
+        ],
+        "tests/expected/the-bustle.txt.nl.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn number_width_too_wide() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--number-width=100000000000", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not in 1..=65535"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn number_sections() -> TestResult {
+    run(
+        &["-n", "--number-sections", "tests/inputs/sections.txt"],
+        "tests/expected/sections.txt.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn color_always() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--color=always", "-nT", "--headers", FOX, "-"])
+        .write_stdin("\tx\n")
+        .assert()
+        .success()
+        .stdout(concat!(
+            "\x1b[1m==> tests/inputs/fox.txt <==\x1b[0m\n",
+            "\x1b[32m     1\t\x1b[0mThe quick brown fox jumps over the lazy dog.\n",
+            "\x1b[1m==> standard input <==\x1b[0m\n",
+            "\x1b[32m     2\t\x1b[0m\x1b[35m^I\x1b[0mx\n",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn color_environment() -> TestResult {
+    let expected = "\x1b[32m     1\t\x1b[0mThe quick brown fox jumps over the lazy dog.";
+    let cases = [
+        (&[][..], "--color=auto", false),
+        (&[("CLICOLOR_FORCE", "1")][..], "--color=auto", true),
+        (&[("CLICOLOR_FORCE", "0")][..], "--color=auto", false),
+        (&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")][..], "--color=auto", false),
+        (&[("NO_COLOR", "1")][..], "--color=always", true),
+        (&[("CLICOLOR_FORCE", "1")][..], "--color=never", false),
+    ];
+    for (vars, flag, colored) in cases {
+        let mut cmd = Command::cargo_bin(PRG)?;
+        cmd.env_remove("NO_COLOR").env_remove("CLICOLOR_FORCE").envs(vars.iter().copied());
+        let output = cmd.args([flag, "-n", FOX]).output()?;
+        let stdout = String::from_utf8(output.stdout)?;
+        assert_eq!(stdout == expected, colored, "{vars:?} {flag}");
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+#[test]
+fn paging_always() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .env("PAGER", "sed s/^/>/")
+        .args(["--paging=always", "-n", SPIDERS])
+        .assert()
+        .success()
+        .stdout(">     1\tDon't worry, spiders,\n>     2\tI keep house\n>     3\tcasually.");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+#[test]
+fn paging_pager_quits() -> TestResult {
+    let input: String = "All work and no play makes Jack a dull boy.\n".repeat(100_000);
+    Command::cargo_bin(PRG)?
+        .env("PAGER", "head -n 1")
+        .arg("--paging=always")
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout("All work and no play makes Jack a dull boy.\n")
+        .stderr("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+#[test]
+fn paging_quoted_arguments() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .env("PAGER", r#"sed "s/^/> /""#)
+        .args(["--paging=always", FOX])
+        .assert()
+        .success()
+        .stdout("> The quick brown fox jumps over the lazy dog.");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+#[test]
+fn paging_less_gets_raw_control_chars() -> TestResult {
+    use std::os::unix::fs::PermissionsExt;
+
+    // A less that only tells what it was given.
+    let dir = gen_temp_dir()?;
+    let less = dir.join("less");
+    fs::write(&less, "#!/bin/sh\necho \"$@\"\ncat >/dev/null\n")?;
+    fs::set_permissions(&less, fs::Permissions::from_mode(0o755))?;
+
+    let result = (|| -> TestResult {
+        for (args, expected) in [("", "-R\n"), (" -F", "-F -R\n"), (" -FR", "-FR\n")] {
+            Command::cargo_bin(PRG)?
+                .env("PAGER", format!("{}{args}", less.display()))
+                .args(["--paging=always", FOX])
+                .assert()
+                .success()
+                .stdout(expected);
+        }
+        Ok(())
+    })();
+    fs::remove_dir_all(&dir)?;
+    result
+}
+
+// --------------------------------------------------
+#[test]
+fn paging_auto_off_terminal() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .env("PAGER", "false")
+        .args(["--paging", FOX])
+        .assert()
+        .success()
+        .stdout(fs::read_to_string(FOX)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(feature = "highlight")]
+#[test]
+fn highlight_after_numbering() -> TestResult {
+    let output = Command::cargo_bin(PRG)?
+        .args(["-n", "--highlight", "tests/inputs/hello.rs", FOX])
+        .output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let lines: Vec<_> = stdout.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("     1\t\x1b[38;2;"));
+    assert!(lines[0].ends_with("\x1b[0m"));
+    assert_eq!(lines[3], "     4\tThe quick brown fox jumps over the lazy dog.");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(feature = "highlight")]
+#[test]
+fn highlight_language() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("--highlight=rust")
+        .write_stdin("fn main() {}\n")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("\x1b[38;2;"));
+    Command::cargo_bin(PRG)?
+        .args(["--highlight=klingon", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no syntax for 'klingon'"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(not(feature = "highlight"))]
+#[test]
+fn highlight_not_compiled_in() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--highlight", FOX])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("without the `highlight` feature"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn squeeze_blank_zero() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--squeeze-blank=0", WHITESPACE])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn all_with_stdin() -> TestResult {
+    run_stdin(SPIDERS, &[FOX, "-", BUSTLE], "tests/expected/all.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn compressed_untouched_without_z() -> TestResult {
+    run(&[FOX_GZ], FOX_GZ)
+}
+
+// --------------------------------------------------
+#[cfg(feature = "gzip")]
+#[test]
+fn gzip_numbers_across_files() -> TestResult {
+    run(&["-n", "-z", FOX_GZ, SPIDERS, BUSTLE], "tests/expected/all.n.out")
+}
+
+// --------------------------------------------------
+#[cfg(feature = "gzip")]
+#[test]
+fn gzip_stdin() -> TestResult {
+    run_stdin(FOX_GZ, &["--decompress"], "tests/expected/fox.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn decompress_plain_files() -> TestResult {
+    run_stdin(SPIDERS, &["-z", FOX, "-", BUSTLE], "tests/expected/all.out")
+}
+
+// --------------------------------------------------
+#[cfg(feature = "bzip2")]
+#[test]
+fn bzip2_n() -> TestResult {
+    run(&["-nz", "tests/inputs/the-bustle.txt.bz2"], "tests/expected/the-bustle.txt.n.out")
+}
+
+// --------------------------------------------------
+#[cfg(feature = "xz")]
+#[test]
+fn xz_n() -> TestResult {
+    run(&["-nz", BUSTLE_XZ], "tests/expected/the-bustle.txt.n.out")
+}
+
+// --------------------------------------------------
+#[cfg(not(feature = "xz"))]
+#[test]
+fn xz_not_compiled_in() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-z", BUSTLE_XZ, FOX])
+        .assert()
+        .code(1)
+        .stdout(fs::read("tests/expected/fox.txt.out")?)
+        .stderr(predicate::str::contains("without the `xz` feature"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd_n() -> TestResult {
+    run(&["-nz", "tests/inputs/the-bustle.txt.zst"], "tests/expected/the-bustle.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn tar_member() -> TestResult {
+    run(&["tests/inputs/release.tar:docs/fox.txt"], "tests/expected/fox.txt.out")?;
+    run(
+        &["-n", "tests/inputs/release.tar:./docs/the-bustle.txt"],
+        "tests/expected/the-bustle.txt.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[cfg(feature = "gzip")]
+#[test]
+fn compressed_tar_member_numbers_across_files() -> TestResult {
+    run(
+        &["-n", "tests/inputs/release.tar.gz:docs/fox.txt", SPIDERS, BUSTLE],
+        "tests/expected/all.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn zip_members() -> TestResult {
+    run(&["tests/inputs/bundle.zip:spiders.txt"], "tests/expected/spiders.txt.out")?;
+    run(&["-b", "tests/inputs/bundle.zip:docs/fox.txt"], "tests/expected/fox.txt.b.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn bad_archive_members() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([
+            "tests/inputs/release.tar:docs/missing.txt",
+            "tests/inputs/bundle.zip:docs/",
+            FOX,
+        ])
+        .assert()
+        .code(1)
+        .stdout(fs::read("tests/expected/fox.txt.out")?)
+        .stderr(
+            "catr: tests/inputs/release.tar:docs/missing.txt: no such member in the archive\n\
+             catr: tests/inputs/bundle.zip:docs/: Is a directory\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn archive_member_flag() -> TestResult {
+    let dir = gen_temp_dir()?;
+    let result = (|| -> TestResult {
+        fs::copy("tests/inputs/bundle.zip", dir.join("bundle.zip"))?;
+        fs::write(dir.join("bundle.zip:spiders.txt"), "not a member\n")?;
+
+        Command::cargo_bin(PRG)?
+            .current_dir(&dir)
+            .arg("bundle.zip:spiders.txt")
+            .assert()
+            .success()
+            .stdout("not a member\n");
+        Command::cargo_bin(PRG)?
+            .current_dir(&dir)
+            .args(["--archive-member", "bundle.zip:spiders.txt"])
+            .assert()
+            .success()
+            .stdout(fs::read("tests/expected/spiders.txt.out")?);
+        Ok(())
+    })();
+    fs::remove_dir_all(&dir)?;
+    result
+}
+
+// --------------------------------------------------
+fn gen_tree() -> Result<PathBuf, Box<dyn Error>> {
+    let dir = gen_temp_dir()?;
+    for sub in ["a", ".github", ".git"] {
+        fs::create_dir_all(dir.join(sub))?;
+    }
+    for (path, contents) in [
+        ("b.txt", "b\n"),
+        ("a/2.txt", "a2\n"),
+        ("a/1.log", "a1\n"),
+        ("c.tmp", "ignored\n"),
+        (".hidden", "hidden\n"),
+        (".github/ci.yml", "ci\n"),
+        (".git/HEAD", "ref: refs/heads/main\n"),
+        (".gitignore", "*.tmp\n"),
+    ] {
+        fs::write(dir.join(path), contents)?;
+    }
+    Ok(dir)
+}
+
+// --------------------------------------------------
+fn run_tree(args: &[&str], expected: &str) -> TestResult {
+    let dir = gen_tree()?;
+    let result = (|| -> TestResult {
+        Command::cargo_bin(PRG)?
+            .current_dir(&dir)
+            .args(args)
+            .assert()
+            .success()
+            .stdout(expected.to_string());
+        Ok(())
+    })();
+    fs::remove_dir_all(&dir)?;
+    result
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive() -> TestResult {
+    run_tree(&["-r", "."], "ci\n*.tmp\nhidden\na1\na2\nb\n")?;
+    run_tree(
+        &["-n", "--recursive", "a", "b.txt", "a/1.log"],
+        "     1\ta1\n     2\ta2\n     3\tb\n     4\ta1\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_include_exclude() -> TestResult {
+    run_tree(&["-r", "--include", "*.txt", "."], "a2\nb\n")?;
+    run_tree(&["-r", "--exclude", "a", "."], "ci\n*.tmp\nhidden\nb\n")?;
+    run_tree(&["-r", "--include", ".hidden", "--include", "*.yml", "."], "ci\nhidden\n")?;
+    run_tree(
+        &["-r", "--include", "*.log", "--include", "b.*", "--exclude", "a/*.txt", "."],
+        "a1\nb\n",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn recursive_bad_pattern() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-r", "--include", "[", FOX])
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr(predicate::str::starts_with("catr: invalid pattern '['"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn headers() -> TestResult {
+    let fox = fs::read_to_string(FOX)?;
+    let spiders = fs::read_to_string(SPIDERS)?;
+    Command::cargo_bin(PRG)?
+        .args(["--headers", FOX, "-"])
+        .write_stdin(spiders.clone())
+        .assert()
+        .success()
+        .stdout(format!(
+            "==> {FOX} <==\n{fox}\n==> standard input <==\n{spiders}"
+        ));
+    run(&["--headers", FOX], "tests/expected/fox.txt.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn headers_end_partial_lines() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-n", "--headers", FOX, SPIDERS])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "lazy dog.\n==> {SPIDERS} <==\n     2\tDon't worry"
+        )));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn headers_box_details() -> TestResult {
+    let path = gen_temp_file("one\n")?;
+    let file = OpenOptions::new().write(true).open(&path)?;
+    file.set_modified(UNIX_EPOCH + Duration::from_secs(1_668_297_600))?;
+    let name = path.to_str().unwrap();
+
+    let label = format!("{name} (4 bytes, modified 2022-11-13T00:00:00Z)");
+    let rule = "─".repeat(label.chars().count() + 2);
+    let header = format!("┌{rule}┐\n│ {label} │\n└{rule}┘\n");
+    Command::cargo_bin(PRG)?
+        .args(["--headers=box", "--header-details", name, name])
+        .assert()
+        .success()
+        .stdout(format!("{header}one\n\n{header}one\n"));
+    fs::remove_file(&path)?;
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_lines(args: &[&str], input: &str, expected: &str) -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected.to_string());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn lines_range() -> TestResult {
+    let input = "a\nb\nc\nd\ne\n";
+    run_lines(&["--lines=2:3"], input, "b\nc\n")?;
+    run_lines(&["-n", "--lines=4:"], input, "     4\td\n     5\te\n")?;
+    run_lines(&["--lines=:1"], input, "a\n")?;
+    run_lines(&["--lines=3"], input, "c\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn lines_from_end() -> TestResult {
+    let input = "a\nb\nc\nd\ne";
+    run_lines(&["-n", "--lines=-2:"], input, "     4\td\n     5\te")?;
+    run_lines(&["--lines=2:-2"], input, "b\nc\nd\n")?;
+    run_lines(&["--lines=-3:-3"], input, "c\n")?;
+    run_lines(&["--lines=-9:2"], input, "a\nb\n")
+}
+
+// --------------------------------------------------
+#[test]
+fn lines_numbered_per_file() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-b", "--lines=3:5", BUSTLE, SPIDERS])
+        .assert()
+        .success()
+        .stdout("     3\tIs solemnest of industries\n     4\tEnacted upon earth,—\n\n     3\tcasually.");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn bytes_range() -> TestResult {
+    let input = "one\ntwo\nthree\n";
+    run_lines(&["--bytes=4:3"], input, "two")?;
+    run_lines(&["-n", "--bytes=6:"], input, "o\n     3\tthree\n")?;
+    run_lines(&["--bytes=:2", "-", BUSTLE], input, "onTh")
+}
+
+// --------------------------------------------------
+#[test]
+fn bad_ranges() -> TestResult {
+    for arg in ["--lines=0:2", "--lines=a:b", "--bytes=-1:2"] {
+        Command::cargo_bin(PRG)?
+            .arg(arg)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("expected"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn hex() -> TestResult {
+    run(&["--hex", FOX, SPIDERS], "tests/expected/fox-spiders.hex.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn hex_bytes_range() -> TestResult {
+    run(&["--hex", "--bytes=3:20", FOX], "tests/expected/fox.txt.hex.bytes.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn hex_repeated_rows() -> TestResult {
+    let mut input = vec![0; 40];
+    input.extend_from_slice(b"abc");
+    Command::cargo_bin(PRG)?
+        .arg("--hex")
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(concat!(
+            "00000000  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n",
+            "*\n",
+            "00000020  00 00 00 00 00 00 00 00  61 62 63                 |........abc|\n",
+            "0000002b\n",
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn decode_base64_n() -> TestResult {
+    run(&["--decode=base64", "-n", "tests/inputs/fox.txt.b64"], "tests/expected/fox.txt.n.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn encode_base64() -> TestResult {
+    run(&["--encode=base64", BUSTLE], "tests/expected/the-bustle.txt.b64.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn encode_decode_round_trip() -> TestResult {
+    for encoding in ["hex", "base64", "base32"] {
+        let encoded = Command::cargo_bin(PRG)?
+            .args([&format!("--encode={encoding}"), LATIN1, BUSTLE])
+            .output()?
+            .stdout;
+        Command::cargo_bin(PRG)?
+            .arg(format!("--decode={encoding}"))
+            .write_stdin(encoded)
+            .assert()
+            .success()
+            .stdout([fs::read(LATIN1)?, fs::read(BUSTLE)?].concat());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn decode_invalid() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--decode=base64", "-", "tests/inputs/fox.txt.b64"])
+        .write_stdin("*!*\n")
+        .assert()
+        .code(1)
+        .stdout(fs::read_to_string(FOX)?)
+        .stderr("catr: -: invalid base64 input\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+fn run_all_bytes(flag: &str, expected_file: &str) -> TestResult {
+    let input: Vec<u8> = (0..=255).collect();
+    let expected = fs::read(expected_file)?;
+    Command::cargo_bin(PRG)?
+        .arg(flag)
+        .write_stdin(input)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn all_bytes_v() -> TestResult {
+    run_all_bytes("-v", "tests/expected/all-bytes.v.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_bytes_show_all() -> TestResult {
+    run_all_bytes("--show-all", "tests/expected/all-bytes.A.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_bytes_t() -> TestResult {
+    run_all_bytes("-t", "tests/expected/all-bytes.t.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn all_bytes_e() -> TestResult {
+    run_all_bytes("-e", "tests/expected/all-bytes.e.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_v() -> TestResult {
+    run(&["-v", LATIN1], "tests/expected/latin1.txt.v.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn latin1_show_all() -> TestResult {
+    run(&["-A", LATIN1], "tests/expected/latin1.txt.A.out")
+}
+
+// --------------------------------------------------
+fn gen_temp_file(contents: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let path = env::temp_dir().join(format!("catr-{}", gen_bad_file()));
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+// --------------------------------------------------
+fn gen_temp_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let dir = env::temp_dir().join(format!("catr-{}", gen_bad_file()));
+    fs::create_dir(&dir)?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+fn append_self(args: &[&str], contents: &str) -> Result<process::Output, Box<dyn Error>> {
+    let path = gen_temp_file(contents)?;
+    let out = OpenOptions::new().append(true).open(&path)?;
+    let output = process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(args)
+        .arg(&path)
+        .stdout(out)
+        .output()?;
+    assert_eq!(fs::read_to_string(&path)?, contents);
+    fs::remove_file(&path)?;
+    Ok(output)
+}
+
+// --------------------------------------------------
+#[test]
+fn input_is_output() -> TestResult {
+    for args in [&[][..], &["-n"][..]] {
+        let output = append_self(args, "some text\n")?;
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8(output.stderr)?.ends_with(": input file is output file\n"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn empty_input_is_output() -> TestResult {
+    let output = append_self(&[], "")?;
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    Ok(())
+}
+
+// --------------------------------------------------
+#[cfg(unix)]
+#[test]
+fn broken_pipe() -> TestResult {
+    use std::os::unix::process::ExitStatusExt;
+
+    for flag in ["-u", "-n"] {
+        let mut child = process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+            .arg(flag)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // Close the reading end before catr gets anything to write.
+        drop(child.stdout.take());
+        child.stdin.take().unwrap().write_all(b"some text\n")?;
+
+        let output = child.wait_with_output()?;
+        assert_eq!(output.status.signal(), Some(13));
+        assert!(output.stderr.is_empty());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+fn expect_output(
+    chunks: &Chunks,
+    output: &mut Vec<u8>,
+    expected: &str,
+) -> TestResult {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while output != expected.as_bytes() {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match chunks.recv_timeout(left) {
+            Ok(chunk) => output.extend(chunk),
+            Err(_) => {
+                let output = String::from_utf8_lossy(output);
+                return Err(format!("got {output:?}, expected {expected:?}").into());
+            }
+        }
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+/// Starts catr with `args` on `path`, handing what it prints over in
+/// chunks as they come.
+fn spawn_follow(
+    args: &[&str],
+    path: &Path,
+    stderr: Stdio,
+) -> Result<(process::Child, Chunks), Box<dyn Error>> {
+    let mut child = process::Command::new(assert_cmd::cargo::cargo_bin(PRG))
+        .args(args)
+        .arg(path)
+        .stdout(Stdio::piped())
+        .stderr(stderr)
+        .spawn()?;
+
+    let mut stdout = child.stdout.take().unwrap();
+    let (tx, chunks) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0; 1024];
+        while let Ok(n @ 1..) = stdout.read(&mut buf) {
+            if tx.send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+    Ok((child, chunks))
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_name() -> TestResult {
+    let path = gen_temp_file("one\n")?;
+    let rotated = path.with_extension("1");
+    let (mut child, chunks) = spawn_follow(&["-n", "--follow=name"], &path, Stdio::null())?;
+
+    let mut output = Vec::new();
+    let result = (|| {
+        expect_output(&chunks, &mut output, "     1\tone\n")?;
+        OpenOptions::new().append(true).open(&path)?.write_all(b"tw")?;
+        expect_output(&chunks, &mut output, "     1\tone\n     2\ttw")?;
+        OpenOptions::new().append(true).open(&path)?.write_all(b"o\n")?;
+        expect_output(&chunks, &mut output, "     1\tone\n     2\ttwo\n")?;
+
+        fs::rename(&path, &rotated)?;
+        fs::write(&path, "three\n")?;
+        expect_output(&chunks, &mut output, "     1\tone\n     2\ttwo\n     3\tthree\n")?;
+
+        fs::write(&path, "x\n")?;
+        expect_output(
+            &chunks,
+            &mut output,
+            "     1\tone\n     2\ttwo\n     3\tthree\n     4\tx\n",
+        )
+    })();
+
+    child.kill()?;
+    child.wait()?;
+    fs::remove_file(&path)?;
+    fs::remove_file(&rotated)?;
+    result
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_descriptor_renamed() -> TestResult {
+    let path = gen_temp_file("one\n")?;
+    let moved = path.with_extension("moved");
+    let (mut child, chunks) = spawn_follow(&["-n", "-f"], &path, Stdio::null())?;
+
+    let mut output = Vec::new();
+    let result = (|| {
+        expect_output(&chunks, &mut output, "     1\tone\n")?;
+        fs::rename(&path, &moved)?;
+        OpenOptions::new().append(true).open(&moved)?.write_all(b"two\n")?;
+        expect_output(&chunks, &mut output, "     1\tone\n     2\ttwo\n")?;
+
+        // The new file with the name is none of its business.
+        fs::write(&path, "new\n")?;
+        OpenOptions::new().append(true).open(&moved)?.write_all(b"three\n")?;
+        expect_output(&chunks, &mut output, "     1\tone\n     2\ttwo\n     3\tthree\n")
+    })();
+
+    child.kill()?;
+    child.wait()?;
+    fs::remove_file(&moved)?;
+    let _ = fs::remove_file(&path);
+    result
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_truncated() -> TestResult {
+    let path = gen_temp_file("one\ntwo\n")?;
+    let (mut child, chunks) = spawn_follow(&["-n", "-f"], &path, Stdio::piped())?;
+
+    let mut output = Vec::new();
+    let result = (|| {
+        expect_output(&chunks, &mut output, "     1\tone\n     2\ttwo\n")?;
+        fs::write(&path, "x\n")?;
+        expect_output(&chunks, &mut output, "     1\tone\n     2\ttwo\n     3\tx\n")?;
+        OpenOptions::new().append(true).open(&path)?.write_all(b"y\n")?;
+        expect_output(&chunks, &mut output, "     1\tone\n     2\ttwo\n     3\tx\n     4\ty\n")
+    })();
+
+    child.kill()?;
+    let stderr = child.wait_with_output()?.stderr;
+    fs::remove_file(&path)?;
+    result?;
+    let expected = format!("catr: {}: file truncated\n", path.display());
+    assert_eq!(String::from_utf8(stderr)?, expected);
+    Ok(())
+}